@@ -1,7 +1,9 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
+use std::thread;
+use std::time::Duration;
 
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
 enum Direction {
@@ -91,23 +93,29 @@ impl Tile {
             Tile::SouthEast => {
                 [Some(Direction::South), Some(Direction::East), None, None].into_iter()
             }
-            Tile::Start => [
-                Some(Direction::North),
-                Some(Direction::South),
-                Some(Direction::East),
-                Some(Direction::West),
-            ]
-            .into_iter(),
-            Tile::Ground | Tile::Inside | Tile::Outside | Tile::Void => {
+            Tile::Start | Tile::Ground | Tile::Inside | Tile::Outside | Tile::Void => {
                 [None, None, None, None].into_iter()
             }
         }
         .flat_map(|maybe_direction| maybe_direction.into_iter())
     }
+
+    fn from_directions(a: Direction, b: Direction) -> Result<Self> {
+        match (a.min(b), a.max(b)) {
+            (Direction::North, Direction::South) => Ok(Tile::Vertical),
+            (Direction::East, Direction::West) => Ok(Tile::Horizontal),
+            (Direction::North, Direction::East) => Ok(Tile::NorthEast),
+            (Direction::North, Direction::West) => Ok(Tile::NorthWest),
+            (Direction::South, Direction::West) => Ok(Tile::SouthWest),
+            (Direction::South, Direction::East) => Ok(Tile::SouthEast),
+            (a, b) => Err(anyhow!("no pipe connects {a:?} and {b:?}")),
+        }
+    }
 }
 
 struct Grid {
     grid: Vec<Vec<Tile>>,
+    start: (usize, usize),
 }
 
 fn parse_tile(c: char) -> Result<Tile> {
@@ -128,41 +136,261 @@ fn parse_line(s: &str) -> Result<Vec<Tile>> {
     s.chars().map(parse_tile).collect()
 }
 
-fn parse_grid() -> Result<Grid> {
-    Ok(Grid {
-        grid: BufReader::new(File::open("./day10/input.txt").context("error opening input")?)
-            .lines()
-            .map(|res| {
-                res.context("error reading input")
-                    .and_then(|line| parse_line(line.as_str()))
-            })
-            .collect::<Result<Vec<_>>>()?,
+fn resolve_start(grid: &mut [Vec<Tile>]) -> Result<(usize, usize)> {
+    let start = grid
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, col)| **col == Tile::Start)
+                .map(move |(x, _)| (x, y))
+        })
+        .next()
+        .context("error finding start")?;
+    let y_max = grid.len() - 1;
+    let x_max = grid[0].len() - 1;
+    let connected = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+    ]
+    .into_iter()
+    .filter(|direction| {
+        direction
+            .go(start.0, start.1, x_max, y_max)
+            .is_some_and(|(x, y)| grid[y][x].directions().any(|d| d.negate() == *direction))
     })
+    .collect::<Vec<_>>();
+    grid[start.1][start.0] = match connected.as_slice() {
+        [a, b] => Tile::from_directions(*a, *b)?,
+        _ => {
+            return Err(anyhow!(
+                "start at {start:?} connects to {connected:?}, expected exactly two pipes"
+            ))
+        }
+    };
+    Ok(start)
+}
+
+fn parse_grid() -> Result<Grid> {
+    let mut grid = BufReader::new(File::open("./day10/input.txt").context("error opening input")?)
+        .lines()
+        .map(|res| {
+            res.context("error reading input")
+                .and_then(|line| parse_line(line.as_str()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let start = resolve_start(grid.as_mut_slice())?;
+    Ok(Grid { grid, start })
+}
+
+fn tile_char(tile: Tile, on_loop: bool) -> char {
+    match (tile, on_loop) {
+        (Tile::Vertical, false) => '│',
+        (Tile::Vertical, true) => '┃',
+        (Tile::Horizontal, false) => '─',
+        (Tile::Horizontal, true) => '━',
+        (Tile::NorthEast, false) => '└',
+        (Tile::NorthEast, true) => '┗',
+        (Tile::NorthWest, false) => '┘',
+        (Tile::NorthWest, true) => '┛',
+        (Tile::SouthWest, false) => '┐',
+        (Tile::SouthWest, true) => '┓',
+        (Tile::SouthEast, false) => '┌',
+        (Tile::SouthEast, true) => '┏',
+        (Tile::Start, _) => 'S',
+        (Tile::Ground, _) => '·',
+        (Tile::Inside, _) => '█',
+        (Tile::Outside | Tile::Void, _) => ' ',
+    }
+}
+
+fn render(tiles: &[Vec<Tile>], on_loop: &HashSet<(usize, usize)>) -> String {
+    let mut frame = String::new();
+    for (y, row) in tiles.iter().enumerate() {
+        frame.extend(
+            row.iter()
+                .enumerate()
+                .map(|(x, tile)| tile_char(*tile, on_loop.contains(&(x, y)))),
+        );
+        frame.push('\n');
+    }
+    frame
+}
+
+fn show(frame: &str, delay: Duration) -> Result<()> {
+    clearscreen::clear().context("error clearing screen")?;
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(frame.as_bytes())
+        .and_then(|()| stdout.flush())
+        .context("error drawing frame")?;
+    thread::sleep(delay);
+    Ok(())
+}
+
+fn visualize(grid: &Grid, delay: Duration, dump: Option<&str>) -> Result<()> {
+    let mut visited = HashSet::from([grid.start]);
+    bfs(grid, |frontier| {
+        visited.extend(frontier.iter().copied());
+        show(render(grid.grid.as_slice(), &visited).as_str(), delay)
+    })?;
+
+    let on_loop = find_loop(grid)?.positions().collect::<HashSet<_>>();
+    let mut tiles = grid.grid.clone();
+    for y in 0..tiles.len() {
+        let mut inside = false;
+        for (x, tile) in tiles[y].iter_mut().enumerate() {
+            if on_loop.contains(&(x, y)) {
+                if tile.directions().any(|d| d == Direction::North) {
+                    inside = !inside;
+                }
+            } else {
+                *tile = if inside { Tile::Inside } else { Tile::Outside };
+            }
+        }
+        show(render(tiles.as_slice(), &on_loop).as_str(), delay)?;
+    }
+
+    if let Some(path) = dump {
+        std::fs::write(path, render(tiles.as_slice(), &on_loop))
+            .with_context(|| format!("error writing frame to {path}"))?;
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let grid = parse_grid()?;
+    if std::env::args().any(|arg| arg == "--visualize") {
+        let arg_value = |name: &str| std::env::args().skip_while(|arg| arg != name).nth(1);
+        let delay = arg_value("--delay-ms")
+            .map(|value| value.parse().context("error parsing --delay-ms"))
+            .transpose()?
+            .unwrap_or(20);
+        visualize(
+            &grid,
+            Duration::from_millis(delay),
+            arg_value("--dump").as_deref(),
+        )?;
+    }
     println!("part1: {}", part1(&grid)?);
-    println!("part2: {}", part2(&grid)?);
+    let enclosed = part2(&grid)?;
+    println!("part2: {enclosed}");
+    if std::env::args().any(|arg| arg == "--cross-check") {
+        let flood_filled = flood_fill_enclosed(&grid)?;
+        if flood_filled != enclosed {
+            return Err(anyhow!(
+                "flood fill found {flood_filled} enclosed tiles, shoelace found {enclosed}"
+            ));
+        }
+        println!("flood fill agrees");
+    }
+    if std::env::args().any(|arg| arg == "--loop") {
+        let main_loop = find_loop(&grid)?;
+        let (distance, farthest) = main_loop.farthest();
+        println!("length: {}", main_loop.len());
+        println!("farthest: {farthest:?} at distance {distance}");
+        println!(
+            "orientation: {}",
+            if main_loop.is_clockwise() {
+                "clockwise"
+            } else {
+                "counterclockwise"
+            }
+        );
+        for ((x, y), direction) in &main_loop.steps {
+            println!("{x},{y} {direction:?}");
+        }
+    }
     Ok(())
 }
 
+struct MainLoop {
+    steps: Vec<((usize, usize), Direction)>,
+}
+
+impl MainLoop {
+    fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    fn positions(&self) -> impl Iterator<Item = (usize, usize)> + Clone + '_ {
+        self.steps.iter().map(|(position, _)| *position)
+    }
+
+    fn farthest(&self) -> (usize, Vec<(usize, usize)>) {
+        let distance = self.len() / 2;
+        let mut tiles = vec![self.steps[distance].0];
+        if self.len() - distance != distance {
+            tiles.push(self.steps[self.len() - distance].0);
+        }
+        (distance, tiles)
+    }
+
+    fn twice_signed_area(&self) -> i64 {
+        self.positions()
+            .zip(self.positions().cycle().skip(1))
+            .map(|((x1, y1), (x2, y2))| (x1 as i64) * (y2 as i64) - (x2 as i64) * (y1 as i64))
+            .sum()
+    }
+
+    fn is_clockwise(&self) -> bool {
+        self.twice_signed_area() > 0
+    }
+
+    fn enclosed(&self) -> usize {
+        let twice_area = self.twice_signed_area().unsigned_abs() as usize;
+        (twice_area + 2).saturating_sub(self.len()) / 2
+    }
+}
+
+fn find_loop(grid: &Grid) -> Result<MainLoop> {
+    let start = grid.start;
+    let y_max = grid.grid.len() - 1;
+    let x_max = grid.grid[0].len() - 1;
+    let connects = |(x, y): (usize, usize), direction: Direction| {
+        direction.go(x, y, x_max, y_max).filter(|(nx, ny)| {
+            grid.grid[*ny][*nx]
+                .directions()
+                .any(|d| d.negate() == direction)
+        })
+    };
+
+    let mut direction = grid.grid[start.1][start.0]
+        .directions()
+        .find(|direction| connects(start, *direction).is_some())
+        .context("start is not connected to any pipe")?;
+    let mut position = start;
+    let mut steps = Vec::new();
+    loop {
+        steps.push((position, direction));
+        position = connects(position, direction)
+            .with_context(|| format!("loop broken at {position:?} going {direction:?}"))?;
+        if position == start {
+            return Ok(MainLoop { steps });
+        }
+        let came_from = direction.negate();
+        direction = grid.grid[position.1][position.0]
+            .directions()
+            .find(|d| *d != came_from)
+            .with_context(|| format!("dead end at {position:?}"))?;
+    }
+}
+
 fn part1(grid: &Grid) -> Result<usize> {
+    bfs(grid, |_| Ok(()))
+}
+
+fn bfs(
+    grid: &Grid,
+    mut on_step: impl FnMut(&HashSet<(usize, usize)>) -> Result<()>,
+) -> Result<usize> {
     let mut next_positions = HashSet::new();
     let mut new_positions = HashSet::new();
     let mut already_visited = HashSet::new();
-    let start = grid
-        .grid
-        .iter()
-        .enumerate()
-        .flat_map(|(y, row)| {
-            row.iter()
-                .enumerate()
-                .filter(|(_, col)| **col == Tile::Start)
-                .map(move |(x, _)| (x, y))
-        })
-        .next()
-        .context("error finding start")?;
+    let start = grid.start;
     let y_max = grid.grid.len() - 1;
     let x_max = grid.grid[0].len() - 1;
     next_positions.insert(start);
@@ -185,6 +413,7 @@ fn part1(grid: &Grid) -> Result<usize> {
             }
         }
         std::mem::swap(&mut new_positions, &mut next_positions);
+        on_step(&next_positions)?;
 
         distance += 1;
     }
@@ -193,6 +422,10 @@ fn part1(grid: &Grid) -> Result<usize> {
 }
 
 fn part2(grid: &Grid) -> Result<usize> {
+    Ok(find_loop(grid)?.enclosed())
+}
+
+fn flood_fill_enclosed(grid: &Grid) -> Result<usize> {
     let y_size = grid.grid.len();
     let x_size = grid.grid[0].len();
     let mut expanded_grid = Vec::new();
@@ -259,19 +492,12 @@ fn part2(grid: &Grid) -> Result<usize> {
     Ok(total)
 }
 
-fn groundify_disconnected(
-    y_size: usize,
-    x_size: usize,
-    expanded_grid: &mut Vec<Vec<Tile>>,
-) -> bool {
+fn groundify_disconnected(y_size: usize, x_size: usize, expanded_grid: &mut [Vec<Tile>]) -> bool {
     let mut did_work = false;
     for j in 0..y_size {
         for i in 0..x_size {
             let x = i * 2;
             let y = j * 2;
-            if expanded_grid[y][x] == Tile::Start {
-                continue;
-            }
             for direction in expanded_grid[y][x].directions() {
                 let junk = if let Some((ni, nj)) = direction.go(i, j, x_size - 1, y_size - 1) {
                     !expanded_grid[nj * 2][ni * 2]
@@ -1,7 +1,10 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 
+const MAX_PHYSICAL_FACTOR: usize = 10;
+
 fn parse_image() -> Result<Vec<Vec<bool>>> {
     let file = File::open("./day11/input.txt")?;
     let reader = BufReader::new(file);
@@ -15,7 +18,7 @@ fn parse_image() -> Result<Vec<Vec<bool>>> {
             match ch {
                 '#' => row.push(true),
                 '.' => row.push(false),
-                _ => return Err(anyhow::anyhow!("Invalid character in image data")),
+                _ => return Err(anyhow!("Invalid character in image data")),
             };
         }
         result.push(row);
@@ -24,50 +27,252 @@ fn parse_image() -> Result<Vec<Vec<bool>>> {
     Ok(result)
 }
 
+#[derive(Copy, Clone)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "euclidean" => Ok(Metric::Euclidean),
+            _ => Err(anyhow!("unknown metric {name:?}")),
+        }
+    }
+
+    fn distance(self, a: (usize, usize), b: (usize, usize)) -> f64 {
+        let rows = a.0.abs_diff(b.0) as f64;
+        let cols = a.1.abs_diff(b.1) as f64;
+        match self {
+            Metric::Manhattan => rows + cols,
+            Metric::Chebyshev => rows.max(cols),
+            Metric::Euclidean => rows.hypot(cols),
+        }
+    }
+}
+
+struct Universe {
+    galaxies: Vec<(usize, usize)>,
+}
+
+impl Universe {
+    fn new(image: &[Vec<bool>], row_factor: usize, col_factor: usize) -> Self {
+        let (rows_expanded, cols_expanded) = expand_image(image);
+        let row_positions = expanded_positions(rows_expanded.as_slice(), row_factor);
+        let col_positions = expanded_positions(cols_expanded.as_slice(), col_factor);
+        Universe {
+            galaxies: galaxy_locations(image)
+                .map(|(row, col)| (row_positions[row], col_positions[col]))
+                .collect(),
+        }
+    }
+
+    fn galaxy(&self, number: usize) -> Result<(usize, usize)> {
+        number
+            .checked_sub(1)
+            .and_then(|idx| self.galaxies.get(idx))
+            .copied()
+            .with_context(|| format!("no galaxy {number}, there are {}", self.galaxies.len()))
+    }
+
+    fn distance(&self, a: usize, b: usize, metric: Metric) -> Result<f64> {
+        Ok(metric.distance(self.galaxy(a)?, self.galaxy(b)?))
+    }
+
+    fn nearest(&self, number: usize, count: usize, metric: Metric) -> Result<Vec<(usize, f64)>> {
+        let origin = self.galaxy(number)?;
+        let mut neighbours = self
+            .galaxies
+            .iter()
+            .enumerate()
+            .map(|(idx, galaxy)| (idx + 1, metric.distance(origin, *galaxy)))
+            .filter(|(other, _)| *other != number)
+            .collect::<Vec<_>>();
+        neighbours.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        neighbours.truncate(count);
+        Ok(neighbours)
+    }
+
+    fn farthest_pair(&self, metric: Metric) -> Option<(usize, usize, f64)> {
+        let mut farthest: Option<(usize, usize, f64)> = None;
+        for (a, first) in self.galaxies.iter().enumerate() {
+            for (b, second) in self.galaxies.iter().enumerate().skip(a + 1) {
+                let distance = metric.distance(*first, *second);
+                if farthest.is_none_or(|(_, _, best)| distance > best) {
+                    farthest = Some((a + 1, b + 1, distance));
+                }
+            }
+        }
+        farthest
+    }
+
+    fn total_manhattan(&self) -> usize {
+        axis_distance_sum(self.galaxies.iter().map(|(row, _)| *row).collect())
+            + axis_distance_sum(self.galaxies.iter().map(|(_, col)| *col).collect())
+    }
+}
+
+fn render(image: &[Vec<bool>], row_factor: usize, col_factor: usize) -> String {
+    let (mut rows_expanded, mut cols_expanded) = expand_image(image);
+    let width = galaxy_locations(image).count().max(1).to_string().len();
+    let physical = row_factor <= MAX_PHYSICAL_FACTOR && col_factor <= MAX_PHYSICAL_FACTOR;
+    if !physical {
+        for (expanded, factor) in [
+            (&mut rows_expanded, row_factor),
+            (&mut cols_expanded, col_factor),
+        ] {
+            if factor <= 1 {
+                expanded.fill(false);
+            }
+        }
+    }
+    let repeats = |expanded: bool, factor: usize| if physical && expanded { factor } else { 1 };
+
+    let mut numbers = galaxy_locations(image)
+        .enumerate()
+        .map(|(idx, location)| (location, idx + 1))
+        .collect::<HashMap<_, _>>();
+    let mut output = String::new();
+    if !physical {
+        output += &format!("rows x{row_factor}, cols x{col_factor}\n  ");
+        for col_expanded in &cols_expanded {
+            let marker = if *col_expanded { 'v' } else { ' ' };
+            output.extend(std::iter::repeat_n(marker, width));
+        }
+        output.push('\n');
+    }
+    for (row, row_expanded) in rows_expanded.iter().enumerate() {
+        let mut line = String::new();
+        if !physical {
+            line.push(if *row_expanded { '>' } else { ' ' });
+            line.push(' ');
+        }
+        for (col, col_expanded) in cols_expanded.iter().enumerate() {
+            let cell = match numbers.remove(&(row, col)) {
+                Some(number) => format!("{number:>width$}"),
+                None => {
+                    let fill = match (physical, *row_expanded, *col_expanded) {
+                        (false, true, true) => '+',
+                        (false, true, false) => '-',
+                        (false, false, true) => '|',
+                        _ => '.',
+                    };
+                    std::iter::repeat_n(fill, width).collect()
+                }
+            };
+            for _ in 0..repeats(*col_expanded, col_factor) {
+                line += &cell;
+            }
+        }
+        line.push('\n');
+        for _ in 0..repeats(*row_expanded, row_factor) {
+            output += &line;
+        }
+    }
+    output
+}
+
+fn parse_pair(value: &str) -> Result<(usize, usize)> {
+    let (a, b) = value
+        .split_once(',')
+        .with_context(|| format!("expected two galaxy numbers like 1,7, got {value:?}"))?;
+    Ok((
+        a.trim().parse().context("error parsing galaxy number")?,
+        b.trim().parse().context("error parsing galaxy number")?,
+    ))
+}
+
 fn main() -> Result<()> {
     let image = parse_image()?;
     println!("part1: {}", part1(image.as_slice()));
     println!("part2: {}", part2(image.as_slice()));
+
+    let arg_value = |name: &str| std::env::args().skip_while(|arg| arg != name).nth(1);
+    let factor = |name: &str| {
+        arg_value(name)
+            .map(|value| {
+                value
+                    .parse::<usize>()
+                    .with_context(|| format!("error parsing {name}"))
+            })
+            .transpose()
+            .map(|factor| factor.unwrap_or(2))
+    };
+    let universe = Universe::new(
+        image.as_slice(),
+        factor("--row-factor")?,
+        factor("--col-factor")?,
+    );
+    let metric = arg_value("--metric")
+        .map(|name| Metric::parse(name.as_str()))
+        .transpose()?
+        .unwrap_or(Metric::Manhattan);
+
+    if let Some(value) = arg_value("--distance") {
+        let (a, b) = parse_pair(value.as_str())?;
+        println!("distance {a} -> {b}: {}", universe.distance(a, b, metric)?);
+    }
+    if let Some(value) = arg_value("--nearest") {
+        let number = value.parse().context("error parsing --nearest")?;
+        let count = arg_value("--count")
+            .map(|value| value.parse().context("error parsing --count"))
+            .transpose()?
+            .unwrap_or(1);
+        for (other, distance) in universe.nearest(number, count, metric)? {
+            println!("nearest to {number}: {other} at {distance}");
+        }
+    }
+    if std::env::args().any(|arg| arg == "--farthest") {
+        match universe.farthest_pair(metric) {
+            Some((a, b, distance)) => println!("farthest pair: {a} and {b} at {distance}"),
+            None => println!("farthest pair: fewer than two galaxies"),
+        }
+    }
+    if std::env::args().any(|arg| arg == "--render") {
+        print!(
+            "{}",
+            render(
+                image.as_slice(),
+                factor("--row-factor")?,
+                factor("--col-factor")?
+            )
+        );
+    }
     Ok(())
 }
 
 fn part1(image: &[Vec<bool>]) -> usize {
-    calc_distances(image, 2)
+    Universe::new(image, 2, 2).total_manhattan()
 }
 
 fn part2(image: &[Vec<bool>]) -> usize {
-    calc_distances(image, 1000000)
+    Universe::new(image, 1000000, 1000000).total_manhattan()
 }
 
-fn calc_distances(image: &[Vec<bool>], expansion_factor: usize) -> usize {
-    let (rows_expanded, cols_expanded) = expand_image(image);
-
-    let row_dist = |row: usize| {
-        if rows_expanded[row] {
-            expansion_factor
-        } else {
-            1
-        }
-    };
-    let col_dist = |col: usize| {
-        if cols_expanded[col] {
-            expansion_factor
-        } else {
-            1
-        }
-    };
-
-    galaxy_locations(image)
-        .clone()
-        .flat_map(|g1| galaxy_locations(image).map(move |g2| (g1, g2)))
-        .filter(|(g1, g2)| *g1 < *g2)
-        .map(|((row1, mut col1), (row2, mut col2))| -> usize {
-            if col1 > col2 {
-                std::mem::swap(&mut col1, &mut col2);
-            }
-            (row1..row2).map(row_dist).sum::<usize>() + (col1..col2).map(col_dist).sum::<usize>()
+fn expanded_positions(expanded: &[bool], expansion_factor: usize) -> Vec<usize> {
+    expanded
+        .iter()
+        .scan(0, |position, is_expanded| {
+            let current = *position;
+            *position += if *is_expanded { expansion_factor } else { 1 };
+            Some(current)
         })
-        .sum()
+        .collect()
+}
+
+fn axis_distance_sum(mut positions: Vec<usize>) -> usize {
+    positions.sort_unstable();
+    let mut preceding = 0;
+    let mut total = 0;
+    for (idx, position) in positions.into_iter().enumerate() {
+        total += position * idx - preceding;
+        preceding += position;
+    }
+    total
 }
 
 fn galaxy_locations(image: &[Vec<bool>]) -> impl Iterator<Item = (usize, usize)> + Clone + '_ {
@@ -30,7 +30,7 @@ fn parse() -> Result<Vec<Field>> {
         if g.is_empty() {
             continue;
         }
-        result.push(Field { data: g });
+        result.push(Field::new(g)?);
     }
 
     Ok(result)
@@ -38,6 +38,8 @@ fn parse() -> Result<Vec<Field>> {
 
 struct Field {
     data: Vec<Vec<bool>>,
+    rows: Vec<u64>,
+    cols: Vec<u64>,
 }
 
 impl fmt::Debug for Field {
@@ -53,96 +55,53 @@ impl fmt::Debug for Field {
 }
 
 impl Field {
-    fn width(&self) -> usize {
-        self.data[0].len()
-    }
-
-    fn height(&self) -> usize {
-        self.data.len()
-    }
-
-    fn row_iter(
-        &self,
-    ) -> impl DoubleEndedIterator<Item = (impl DoubleEndedIterator<Item = bool> + '_)> + Clone + '_
-    {
-        self.data.iter().map(|row| row.iter().copied())
-    }
-
-    fn col_iter(
-        &self,
-    ) -> impl DoubleEndedIterator<Item = (impl DoubleEndedIterator<Item = bool> + '_)> + Clone + '_
-    {
-        (0..self.width()).map(|col| self.data.iter().map(move |row| row[col]))
-    }
-
-    fn find_reflection(
-        &self,
-        predicate: impl Fn(Direction, usize) -> bool + Clone,
-    ) -> Option<(Direction, usize)> {
-        fn inner<'a>(
-            size: usize,
-            iter: impl DoubleEndedIterator<Item = (impl DoubleEndedIterator<Item = bool> + 'a)>
-                + Clone
-                + 'a,
-            predicate: impl Fn(Direction, usize) -> bool,
-            direction: Direction,
-        ) -> Option<(Direction, usize)> {
-            for pivot in 1..size {
-                if predicate(direction, pivot)
-                    && iter
-                        .clone()
-                        .map(|segment| segment.rev().skip(size - pivot))
-                        .zip(iter.clone().map(|segment| segment.skip(pivot)))
-                        .all(|(li, ri)| li.zip(ri).all(|(l, r)| l == r))
-                {
-                    return Some((direction, pivot));
-                }
-            }
-
-            None
+    fn new(data: Vec<Vec<bool>>) -> Result<Self> {
+        let width = data[0].len();
+        if data.iter().any(|row| row.len() != width) {
+            return Err(anyhow!("rows of differing widths in field"));
         }
-
-        if let Some(result) = inner(
-            self.width(),
-            self.row_iter(),
-            predicate.clone(),
-            Direction::Vertical,
-        ) {
-            return Some(result);
+        if width > u64::BITS as usize || data.len() > u64::BITS as usize {
+            return Err(anyhow!(
+                "field of {width}x{} exceeds {} cells per side",
+                data.len(),
+                u64::BITS
+            ));
         }
 
-        inner(
-            self.height(),
-            self.col_iter(),
-            predicate,
-            Direction::Horizontal,
-        )
+        let encode = |bits: &mut dyn Iterator<Item = bool>| {
+            bits.fold(0u64, |mask, bit| mask << 1 | u64::from(bit))
+        };
+        let rows = data
+            .iter()
+            .map(|row| encode(&mut row.iter().copied()))
+            .collect();
+        let cols = (0..width)
+            .map(|col| encode(&mut data.iter().map(|row| row[col])))
+            .collect();
+        Ok(Field { data, rows, cols })
     }
 
-    fn find_smudge_reflection(&mut self) -> Option<(Direction, usize)> {
-        for r in 0..self.height() {
-            for c in 0..self.width() {
-                self.data[r][c] = !self.data[r][c];
-                let maybe_smudge = self.find_reflection(|d, o| self.is_reflected(r, c, d, o));
-                self.data[r][c] = !self.data[r][c];
-                if maybe_smudge.is_some() {
-                    return maybe_smudge;
-                }
-            }
-        }
-        None
+    fn find_reflection(&self, smudges: u32) -> Option<(Direction, usize)> {
+        find_mirror(self.cols.as_slice(), smudges)
+            .map(|pivot| (Direction::Vertical, pivot))
+            .or_else(|| {
+                find_mirror(self.rows.as_slice(), smudges)
+                    .map(|pivot| (Direction::Horizontal, pivot))
+            })
     }
+}
 
-    fn is_reflected(&self, r: usize, c: usize, direction: Direction, offset: usize) -> bool {
-        let (potentially_irrelevant, size) = match direction {
-            Direction::Vertical => (c, self.width()),
-            Direction::Horizontal => (r, self.height()),
-        };
-        let range = usize::min(size - offset, offset);
-        let low = offset - range;
-        let high = offset + range;
-        (low..high).contains(&potentially_irrelevant)
-    }
+fn find_mirror(lines: &[u64], smudges: u32) -> Option<usize> {
+    (1..lines.len()).find(|pivot| {
+        let mut differences = 0;
+        for (before, after) in lines[..*pivot].iter().rev().zip(&lines[*pivot..]) {
+            differences += (before ^ after).count_ones();
+            if differences > smudges {
+                return false;
+            }
+        }
+        differences == smudges
+    })
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -166,25 +125,29 @@ fn do_part<T>(
         .sum::<Result<usize>>()
 }
 
-fn part1(fields: &[Field]) -> Result<usize> {
+fn reflection_total(fields: &[Field], smudges: u32) -> Result<usize> {
     do_part(fields.iter(), |field| {
-        field
-            .find_reflection(|_, _| true)
-            .with_context(|| anyhow!("unable to find reflection for:\n{field:?}"))
+        field.find_reflection(smudges).with_context(|| {
+            anyhow!("unable to find reflection with {smudges} smudges for:\n{field:?}")
+        })
     })
 }
 
-fn part2(fields: &mut [Field]) -> Result<usize> {
-    do_part(fields.iter_mut(), |field| {
-        field
-            .find_smudge_reflection()
-            .with_context(|| anyhow!("unable to find smudge reflection for:\n{field:?}"))
-    })
+fn part1(fields: &[Field]) -> Result<usize> {
+    reflection_total(fields, 0)
+}
+
+fn part2(fields: &[Field]) -> Result<usize> {
+    reflection_total(fields, 1)
 }
 
 fn main() -> Result<()> {
-    let mut fields = parse()?;
+    let fields = parse()?;
     println!("part 1: {}", part1(&fields)?);
-    println!("part 2: {}", part2(&mut fields)?);
+    println!("part 2: {}", part2(&fields)?);
+    if let Some(smudges) = std::env::args().skip_while(|arg| arg != "--smudges").nth(1) {
+        let smudges = smudges.parse().context("error parsing --smudges")?;
+        println!("{smudges} smudges: {}", reflection_total(&fields, smudges)?);
+    }
     Ok(())
 }
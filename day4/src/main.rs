@@ -30,7 +30,6 @@ fn parse_cards() -> Result<Vec<Card>> {
 
     let file = CardsParser::parse(Rule::file, text.as_str())
         .context("error parsing file")?
-        .into_iter()
         .next()
         .context("error getting file")?;
 
@@ -1,10 +1,8 @@
 use anyhow::{anyhow, Context, Result};
-use gcollections::ops::{Bounded, Difference, Empty, Intersection, Union};
-use interval::ops::Range;
-use interval::IntervalSet;
 use pest::Parser;
 use pest_derive::Parser;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
 use std::ops::RangeInclusive;
@@ -15,6 +13,7 @@ pub struct MapsParser;
 
 struct Maps {
     seeds: Vec<usize>,
+    seeds_line: usize,
     maps: HashMap<String, (String, Vec<Map>)>,
 }
 
@@ -23,7 +22,67 @@ struct Map {
     output: usize,
 }
 
-fn parse_maps() -> Result<Maps> {
+enum Issue {
+    Overlap {
+        map: String,
+        line: usize,
+        other_line: usize,
+    },
+    ZeroLength {
+        map: String,
+        line: usize,
+    },
+    Overflow {
+        map: String,
+        line: usize,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Overlap {
+                map,
+                line,
+                other_line,
+            } => write!(
+                f,
+                "{map} map line {line}: source range overlaps line {other_line}"
+            ),
+            Issue::ZeroLength { map, line } => {
+                write!(f, "{map} map line {line}: zero length range")
+            }
+            Issue::Overflow { map, line } => {
+                write!(f, "{map} map line {line}: range overflows")
+            }
+        }
+    }
+}
+
+fn find_overlaps(name: &str, m: &[Map], lines: &[usize], issues: &mut Vec<Issue>) {
+    let mut order = (0..m.len()).collect::<Vec<_>>();
+    order.sort_by_key(|idx| *m[*idx].input.start());
+    let mut furthest: Option<usize> = None;
+    for idx in order {
+        if let Some(prev) = furthest {
+            if m[prev].input.end() >= m[idx].input.start() {
+                let (first, second) = (lines[prev].min(lines[idx]), lines[prev].max(lines[idx]));
+                issues.push(Issue::Overlap {
+                    map: name.to_owned(),
+                    line: second,
+                    other_line: first,
+                });
+            }
+            if m[idx].input.end() > m[prev].input.end() {
+                furthest = Some(idx);
+            }
+        } else {
+            furthest = Some(idx);
+        }
+    }
+}
+
+fn parse_maps(strict: bool) -> Result<Maps> {
     let mut text = String::new();
     File::open("day5/input.txt")
         .context("error opening file")?
@@ -38,31 +97,54 @@ fn parse_maps() -> Result<Maps> {
     let mut seeds = Vec::new();
 
     let mut main_iter = file.into_inner();
-    for seed in main_iter
-        .next()
-        .context("error getting seeds")?
-        .into_inner()
-    {
+    let seeds_pair = main_iter.next().context("error getting seeds")?;
+    let seeds_line = seeds_pair.line_col().0;
+    for seed in seeds_pair.into_inner() {
         seeds.push(seed.as_str().parse().context("error parsing seed")?);
     }
 
     let mut maps = HashMap::new();
+    let mut issues = Vec::new();
 
     for map in main_iter {
         match map.as_rule() {
-            Rule::EOI => return Ok(Maps { seeds, maps }),
+            Rule::EOI => {
+                if issues.is_empty() {
+                    return Ok(Maps {
+                        seeds,
+                        seeds_line,
+                        maps,
+                    });
+                }
+                let report = issues
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if strict {
+                    return Err(anyhow!("invalid almanac:\n{report}"));
+                }
+                eprintln!("{report}");
+                return Ok(Maps {
+                    seeds,
+                    seeds_line,
+                    maps,
+                });
+            }
             Rule::map => {
                 let mut map_iter = map.into_inner().peekable();
                 let from = map_iter.next().context("error getting from")?;
                 let to = map_iter.next().context("error getting to")?;
+                let name = format!("{}-to-{}", from.as_str(), to.as_str());
                 let mut m = Vec::new();
+                let mut lines = Vec::new();
                 loop {
                     if map_iter.peek().is_none() {
                         break;
                     }
-                    let to_start: usize = map_iter
-                        .next()
-                        .context("error getting from start")?
+                    let to_start_pair = map_iter.next().context("error getting from start")?;
+                    let line = to_start_pair.line_col().0;
+                    let to_start: usize = to_start_pair
                         .as_str()
                         .parse()
                         .context("error parsing from start")?;
@@ -78,11 +160,30 @@ fn parse_maps() -> Result<Maps> {
                         .as_str()
                         .parse()
                         .context("error parsing len")?;
+                    if len == 0 {
+                        issues.push(Issue::ZeroLength {
+                            map: name.clone(),
+                            line,
+                        });
+                        continue;
+                    }
+                    let (Some(from_end), Some(_)) = (
+                        from_start.checked_add(len - 1),
+                        to_start.checked_add(len - 1),
+                    ) else {
+                        issues.push(Issue::Overflow {
+                            map: name.clone(),
+                            line,
+                        });
+                        continue;
+                    };
                     m.push(Map {
-                        input: from_start..=(from_start + len - 1),
+                        input: from_start..=from_end,
                         output: to_start,
                     });
+                    lines.push(line);
                 }
+                find_overlaps(name.as_str(), m.as_slice(), lines.as_slice(), &mut issues);
                 maps.insert(from.as_str().to_owned(), (to.as_str().to_owned(), m));
             }
             unexpected => return Err(anyhow!("unexpected {unexpected:?}")),
@@ -93,7 +194,8 @@ fn parse_maps() -> Result<Maps> {
 }
 
 fn main() -> Result<()> {
-    let maps = parse_maps()?;
+    let strict = std::env::args().any(|arg| arg == "--strict");
+    let maps = parse_maps(strict)?;
     println!("part1: {}", part1(&maps)?);
     println!("part2: {}", part2(&maps)?);
     Ok(())
@@ -129,35 +231,246 @@ fn part1(maps: &Maps) -> Result<usize> {
 fn part2(maps: &Maps) -> Result<usize> {
     let mut item = "seed";
 
-    let mut items = IntervalSet::empty();
     let mut seed_iter = maps.seeds.iter().copied().fuse();
+    let mut seeds = Vec::new();
     while let (Some(start), Some(len)) = (seed_iter.next(), seed_iter.next()) {
-        items = items.union(&IntervalSet::new(start, start + len - 1));
+        if len > 0 {
+            let end = start.checked_add(len - 1).with_context(|| {
+                format!(
+                    "seeds line {}: range {start} {len} overflows",
+                    maps.seeds_line
+                )
+            })?;
+            seeds.push((start, end));
+        }
     }
+    let mut items: IntervalSet = seeds.into_iter().collect();
 
     while item != "location" {
-        let mut new_items = IntervalSet::empty();
         let (new_item, m) = maps.maps.get(item).context("error getting item")?;
 
-        let mut unmapped = items.clone();
+        let mut new_items = Vec::new();
         for map in m {
             let mapped_range = IntervalSet::new(*map.input.start(), *map.input.end());
-            for mapped in items.intersection(&mapped_range) {
-                let new_range_start = mapped.lower() - *map.input.start() + map.output;
-                let new_range_end = mapped.upper() - *map.input.start() + map.output;
-                new_items = new_items.union(&IntervalSet::new(new_range_start, new_range_end));
-            }
-            unmapped = unmapped.difference(&mapped_range);
+            new_items.extend(
+                items
+                    .intersection(&mapped_range)
+                    .shift(*map.input.start(), map.output)
+                    .iter(),
+            );
         }
+        let sources: IntervalSet = m
+            .iter()
+            .map(|map| (*map.input.start(), *map.input.end()))
+            .collect();
+        let unmapped = items.difference(&sources);
 
-        new_items = new_items.union(&unmapped);
         item = new_item.as_str();
-        items = new_items;
+        items = new_items
+            .into_iter()
+            .collect::<IntervalSet>()
+            .union(&unmapped);
+    }
+
+    items.lower().context("no items left")
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct IntervalSet {
+    intervals: Vec<(usize, usize)>,
+}
+
+impl IntervalSet {
+    fn new(lower: usize, upper: usize) -> Self {
+        IntervalSet {
+            intervals: vec![(lower, upper)],
+        }
+    }
+
+    fn lower(&self) -> Option<usize> {
+        self.intervals.first().map(|(lower, _)| *lower)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.intervals.iter().copied()
+    }
+
+    fn push(&mut self, lower: usize, upper: usize) {
+        if let Some((_, last_upper)) = self.intervals.last_mut() {
+            if lower <= last_upper.saturating_add(1) {
+                *last_upper = usize::max(*last_upper, upper);
+                return;
+            }
+        }
+        self.intervals.push((lower, upper));
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        let mut result = IntervalSet::default();
+        let mut left = self.iter().peekable();
+        let mut right = other.iter().peekable();
+        loop {
+            let next = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) if l.0 <= r.0 => left.next(),
+                (Some(_), Some(_)) => right.next(),
+                (Some(_), None) => left.next(),
+                (None, Some(_)) => right.next(),
+                (None, None) => return result,
+            };
+            if let Some((lower, upper)) = next {
+                result.push(lower, upper);
+            }
+        }
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        let mut result = IntervalSet::default();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (l_lower, l_upper) = self.intervals[i];
+            let (r_lower, r_upper) = other.intervals[j];
+            let lower = usize::max(l_lower, r_lower);
+            let upper = usize::min(l_upper, r_upper);
+            if lower <= upper {
+                result.push(lower, upper);
+            }
+            if l_upper < r_upper {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        result
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        let mut result = IntervalSet::default();
+        let mut j = 0;
+        for (mut lower, upper) in self.iter() {
+            while j < other.intervals.len() && other.intervals[j].1 < lower {
+                j += 1;
+            }
+            let mut k = j;
+            let mut exhausted = false;
+            while k < other.intervals.len() && other.intervals[k].0 <= upper {
+                let (r_lower, r_upper) = other.intervals[k];
+                if r_lower > lower {
+                    result.push(lower, r_lower - 1);
+                }
+                if r_upper >= upper {
+                    exhausted = true;
+                    break;
+                }
+                lower = r_upper + 1;
+                k += 1;
+            }
+            if !exhausted {
+                result.push(lower, upper);
+            }
+        }
+        result
+    }
+
+    fn shift(&self, from: usize, to: usize) -> Self {
+        IntervalSet {
+            intervals: self
+                .iter()
+                .map(|(lower, upper)| (lower - from + to, upper - from + to))
+                .collect(),
+        }
+    }
+}
+
+impl FromIterator<(usize, usize)> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = (usize, usize)>>(iter: T) -> Self {
+        let mut intervals = iter
+            .into_iter()
+            .filter(|(lower, upper)| lower <= upper)
+            .collect::<Vec<_>>();
+        intervals.sort_unstable();
+        let mut result = IntervalSet::default();
+        for (lower, upper) in intervals {
+            result.push(lower, upper);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntervalSet;
+
+    const UNIVERSE: usize = 64;
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    fn random_set(rng: &mut Rng) -> IntervalSet {
+        (0..rng.below(6))
+            .map(|_| {
+                let lower = rng.below(UNIVERSE);
+                (lower, usize::min(lower + rng.below(12), UNIVERSE - 1))
+            })
+            .collect()
+    }
+
+    fn bitmap(set: &IntervalSet) -> u64 {
+        set.iter()
+            .flat_map(|(lower, upper)| lower..=upper)
+            .fold(0, |bits, n| bits | 1 << n)
+    }
+
+    fn assert_normalised(set: &IntervalSet) {
+        for (lower, upper) in set.iter() {
+            assert!(lower <= upper, "{set:?}");
+        }
+        for pair in set.intervals.windows(2) {
+            assert!(pair[0].1 + 1 < pair[1].0, "{set:?}");
+        }
     }
 
-    items
-        .into_iter()
-        .map(|interval| interval.lower())
-        .min()
-        .context("no items left")
+    #[test]
+    fn set_operations_match_bitmap() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10_000 {
+            let (a, b) = (random_set(&mut rng), random_set(&mut rng));
+            let (a_bits, b_bits) = (bitmap(&a), bitmap(&b));
+            for (result, expected) in [
+                (a.union(&b), a_bits | b_bits),
+                (a.intersection(&b), a_bits & b_bits),
+                (a.difference(&b), a_bits & !b_bits),
+            ] {
+                assert_normalised(&result);
+                assert_eq!(bitmap(&result), expected, "{a:?} {b:?} -> {result:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn shift_matches_bitmap() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..10_000 {
+            let set = random_set(&mut rng).intersection(&IntervalSet::new(16, 47));
+            let to = rng.below(32);
+            let shifted = set.shift(16, to);
+            assert_normalised(&shifted);
+            assert_eq!(
+                bitmap(&shifted),
+                bitmap(&set) >> 16 << to,
+                "{set:?} -> {to}"
+            );
+        }
+    }
 }
@@ -1,31 +1,106 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use num_bigint::BigUint;
 use std::fmt::Write;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 struct Race {
-    time: usize,
-    distance: usize,
+    time: BigUint,
+    distance: BigUint,
+}
+
+impl Race {
+    fn ways_to_win(&self) -> BigUint {
+        let squared_time = &self.time * &self.time;
+        let four_distance = &self.distance * 4u32;
+        if four_distance > squared_time {
+            return BigUint::ZERO;
+        }
+        let discriminant = squared_time - four_distance;
+        let mut max_offset = discriminant.sqrt();
+        if &max_offset * &max_offset == discriminant {
+            if max_offset == BigUint::ZERO {
+                return BigUint::ZERO;
+            }
+            max_offset -= 1u32;
+        }
+        if max_offset.bit(0) != self.time.bit(0) {
+            if max_offset == BigUint::ZERO {
+                return BigUint::ZERO;
+            }
+            max_offset -= 1u32;
+        }
+        max_offset + 1u32
+    }
+}
+
+fn parse_row(line_no: usize, line: &str, header: &str) -> Result<Vec<BigUint>> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.strip_prefix(header).with_context(|| {
+        format!(
+            "line {line_no}, column {}: expected {header:?} header",
+            line.len() - trimmed.len() + 1
+        )
+    })?;
+    let rest_offset = line.len() - rest.len();
+    let mut values = Vec::new();
+    let mut column = 0;
+    for (idx, c) in rest.char_indices().chain([(rest.len(), ' ')]) {
+        if c.is_ascii_whitespace() {
+            if column < idx {
+                values.push(rest[column..idx].parse().with_context(|| {
+                    format!(
+                        "line {line_no}, column {}: error parsing {:?}",
+                        rest_offset + column + 1,
+                        &rest[column..idx]
+                    )
+                })?);
+            }
+            column = idx + c.len_utf8();
+        }
+    }
+    if values.is_empty() {
+        return Err(anyhow!("line {line_no}: no values after {header:?}"));
+    }
+    Ok(values)
 }
 
 fn parse_races() -> Result<Vec<Race>> {
     let file = File::open("day6/input.txt").context("error opening input")?;
-    let lines = BufReader::new(file)
-        .lines()
-        .map(|result| result.context("error reading input"))
-        .collect::<Result<Vec<String>>>()?;
-    let mut races = Vec::new();
-    for (time, dist) in lines[0]
-        .split_ascii_whitespace()
-        .skip(1)
-        .zip(lines[1].split_ascii_whitespace().skip(1))
-    {
-        races.push(Race {
-            time: time.parse().context("error parsing time")?,
-            distance: dist.parse().context("error parsing distance")?,
-        });
+    let mut lines = BufReader::new(file).lines().enumerate();
+    let mut next_row = |header: &str| -> Result<(usize, Vec<BigUint>)> {
+        let (idx, result) = lines
+            .next()
+            .with_context(|| format!("unexpected end of input, expected {header:?}"))?;
+        let line = result.context("error reading input")?;
+        if line.trim().is_empty() {
+            return Err(anyhow!(
+                "line {}: unexpected blank line, expected {header:?}",
+                idx + 1
+            ));
+        }
+        Ok((idx + 1, parse_row(idx + 1, line.as_str(), header)?))
+    };
+    let (time_line, times) = next_row("Time:")?;
+    let (distance_line, distances) = next_row("Distance:")?;
+    if times.len() != distances.len() {
+        return Err(anyhow!(
+            "line {distance_line}: found {} distances but line {time_line} has {} times",
+            distances.len(),
+            times.len()
+        ));
     }
-    Ok(races)
+    for (idx, result) in lines {
+        if !result.context("error reading input")?.trim().is_empty() {
+            return Err(anyhow!("line {}: unexpected trailing input", idx + 1));
+        }
+    }
+
+    Ok(times
+        .into_iter()
+        .zip(distances)
+        .map(|(time, distance)| Race { time, distance })
+        .collect())
 }
 
 fn main() -> Result<()> {
@@ -35,38 +110,62 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn part1(races: &[Race]) -> Result<usize> {
-    Ok(races
-        .iter()
-        .map(|race| {
-            let mut ways_to_win = 0;
-            for turns_held in 1..race.time {
-                if turns_held * (race.time - turns_held) > race.distance {
-                    ways_to_win += 1;
-                }
-            }
-            ways_to_win
-        })
-        .product())
+fn part1(races: &[Race]) -> Result<BigUint> {
+    Ok(races.iter().map(Race::ways_to_win).product())
 }
 
-fn part2(races: &[Race]) -> Result<usize> {
+fn part2(races: &[Race]) -> Result<BigUint> {
     let mut buffer = String::new();
     for race in races {
         write!(&mut buffer, "{}", race.time).context("no")?;
     }
-    let time: usize = buffer.parse().context("error parsing time")?;
+    let time: BigUint = buffer.parse().context("error parsing time")?;
     buffer.clear();
     for race in races {
         write!(&mut buffer, "{}", race.distance).context("no")?;
     }
-    let distance: usize = buffer.parse().context("error parsing time")?;
+    let distance: BigUint = buffer.parse().context("error parsing distance")?;
 
-    let mut ways_to_win = 0;
-    for turns_held in 1..time {
-        if turns_held * (time - turns_held) > distance {
-            ways_to_win += 1;
+    Ok(Race { time, distance }.ways_to_win())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Race;
+    use num_bigint::BigUint;
+
+    fn brute_force(time: u64, distance: u64) -> u64 {
+        (0..=time)
+            .filter(|held| held * (time - held) > distance)
+            .count() as u64
+    }
+
+    fn closed_form(time: u64, distance: u64) -> BigUint {
+        Race {
+            time: BigUint::from(time),
+            distance: BigUint::from(distance),
         }
+        .ways_to_win()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for time in 0..80 {
+            for distance in 0..1700 {
+                assert_eq!(
+                    closed_form(time, distance),
+                    BigUint::from(brute_force(time, distance)),
+                    "time {time}, distance {distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exact_roots_and_zero_time() {
+        assert_eq!(closed_form(4, 4), BigUint::ZERO);
+        assert_eq!(closed_form(4, 3), BigUint::from(1u32));
+        assert_eq!(closed_form(30, 200), BigUint::from(9u32));
+        assert_eq!(closed_form(0, 0), BigUint::ZERO);
     }
-    Ok(ways_to_win)
 }
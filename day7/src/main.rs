@@ -1,10 +1,13 @@
 use anyhow::{anyhow, Context, Result};
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::cmp::{Ordering, Reverse};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
+#[cfg(feature = "z3")]
 use std::ops::Add;
+#[cfg(feature = "z3")]
 use z3::ast::{Ast, Bool, Int};
+#[cfg(feature = "z3")]
 use z3::SatResult;
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone, Debug)]
@@ -25,6 +28,23 @@ enum Card {
 }
 
 impl Card {
+    const ALL: [Card; 13] = [
+        Card::Ace,
+        Card::King,
+        Card::Queen,
+        Card::Jack,
+        Card::Ten,
+        Card::Nine,
+        Card::Eight,
+        Card::Seven,
+        Card::Six,
+        Card::Five,
+        Card::Four,
+        Card::Three,
+        Card::Two,
+    ];
+
+    #[cfg(feature = "z3")]
     fn joker_value(self) -> u8 {
         match self {
             Card::Ace => 1,
@@ -44,255 +64,660 @@ impl Card {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+impl TryFrom<char> for Card {
+    type Error = anyhow::Error;
+
+    fn try_from(value: char) -> Result<Self> {
+        Ok(match value {
+            'A' => Card::Ace,
+            'K' => Card::King,
+            'Q' => Card::Queen,
+            'J' => Card::Jack,
+            'T' => Card::Ten,
+            '9' => Card::Nine,
+            '8' => Card::Eight,
+            '7' => Card::Seven,
+            '6' => Card::Six,
+            '5' => Card::Five,
+            '4' => Card::Four,
+            '3' => Card::Three,
+            '2' => Card::Two,
+            unexpected => return Err(anyhow!("unknown card {unexpected:?}")),
+        })
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Card::Ace => 'A',
+            Card::King => 'K',
+            Card::Queen => 'Q',
+            Card::Jack => 'J',
+            Card::Ten => 'T',
+            Card::Nine => '9',
+            Card::Eight => '8',
+            Card::Seven => '7',
+            Card::Six => '6',
+            Card::Five => '5',
+            Card::Four => '4',
+            Card::Three => '3',
+            Card::Two => '2',
+        };
+        write!(f, "{label}")
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
 struct Hand {
-    cards: [Card; 5],
+    cards: Vec<Card>,
     bid: usize,
 }
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Debug)]
-enum HandKind {
-    FiveOfAKind,
-    FourOfAKind,
-    FullHouse,
-    ThreeOfAKind,
-    TwoPair,
-    OnePair,
-    HighCard,
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct HandKind {
+    name: String,
+    groups: Vec<usize>,
 }
 
-impl Hand {
-    fn kind(self) -> HandKind {
-        let mut hand = HashMap::new();
-        for card in self.cards {
-            *hand.entry(card).or_default() += 1;
+impl HandKind {
+    fn new(name: &str, groups: &[usize]) -> Self {
+        let mut groups = groups.to_vec();
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        HandKind {
+            name: name.to_owned(),
+            groups,
         }
-        match hand.values().max().unwrap() {
-            5 => HandKind::FiveOfAKind,
-            4 => HandKind::FourOfAKind,
-            3 => {
-                if *hand.values().filter(|v| **v != 3).max().unwrap() == 2 {
-                    HandKind::FullHouse
-                } else {
-                    HandKind::ThreeOfAKind
-                }
+    }
+
+    fn wilds_needed(&self, counts: &[usize]) -> usize {
+        self.groups
+            .iter()
+            .enumerate()
+            .map(|(idx, group)| group.saturating_sub(counts.get(idx).copied().unwrap_or(0)))
+            .sum()
+    }
+}
+
+struct RuleSet {
+    order: Vec<Card>,
+    wild: Vec<Card>,
+    hand_size: usize,
+    kinds: Vec<HandKind>,
+    strengths: [Option<u32>; 13],
+    wilds: [bool; 13],
+}
+
+fn bits_for(values: usize) -> u32 {
+    usize::BITS - values.saturating_sub(1).leading_zeros()
+}
+
+impl RuleSet {
+    fn new(
+        order: Vec<Card>,
+        wild: Vec<Card>,
+        hand_size: usize,
+        kinds: Vec<HandKind>,
+    ) -> Result<Self> {
+        if order.is_empty() {
+            return Err(anyhow!("no card order given"));
+        }
+        for (idx, card) in order.iter().enumerate() {
+            if order[..idx].contains(card) {
+                return Err(anyhow!("{card:?} appears more than once in card order"));
             }
-            2 => {
-                if hand.values().filter(|v| **v == 2).count() == 2 {
-                    HandKind::TwoPair
-                } else {
-                    HandKind::OnePair
-                }
+        }
+        if let Some(card) = wild.iter().find(|card| !order.contains(card)) {
+            return Err(anyhow!("wild card {card:?} is missing from card order"));
+        }
+        if hand_size == 0 {
+            return Err(anyhow!("hand size must be positive"));
+        }
+        if kinds.is_empty() {
+            return Err(anyhow!("no hand kinds given"));
+        }
+        for kind in &kinds {
+            if kind.groups.is_empty() || kind.groups.contains(&0) {
+                return Err(anyhow!("hand kind {:?} has an empty group", kind.name));
+            }
+            if kind.groups.iter().sum::<usize>() > hand_size {
+                return Err(anyhow!(
+                    "hand kind {:?} needs more than {hand_size} cards",
+                    kind.name
+                ));
+            }
+        }
+        for (idx, weaker) in kinds.iter().enumerate() {
+            if let Some(stronger) = kinds[idx + 1..]
+                .iter()
+                .find(|stronger| stronger.wilds_needed(weaker.groups.as_slice()) == 0)
+            {
+                return Err(anyhow!(
+                    "every {:?} hand is also a {:?} hand, list kinds from weakest to strongest",
+                    weaker.name,
+                    stronger.name
+                ));
             }
-            _ => HandKind::HighCard,
         }
+        let key_bits = bits_for(kinds.len()) as usize + bits_for(order.len()) as usize * hand_size;
+        if key_bits > u32::BITS as usize {
+            return Err(anyhow!(
+                "rule set needs {key_bits} bit keys, at most 32 allowed"
+            ));
+        }
+        Ok(RuleSet::build(order, wild, hand_size, kinds))
     }
 
-    fn joker_kind(self) -> HandKind {
-        fn any_total<'a>(
-            ctx: &'a z3::Context,
-            totals: &'a [Int<'a>],
-            cond: impl Fn(&'a Int<'a>) -> Bool<'a>,
-        ) -> Bool<'a> {
-            let f = Bool::from_bool(&ctx, false);
-            let mut any = f.clone();
-            for total in totals {
-                let res = cond(total);
-                any = any._eq(&f).ite(&res._eq(&f), &f).not();
-            }
-            any
+    fn build(order: Vec<Card>, wild: Vec<Card>, hand_size: usize, kinds: Vec<HandKind>) -> Self {
+        let mut strengths = [None; 13];
+        for (strength, card) in order.iter().enumerate() {
+            strengths[*card as usize] = Some(strength as u32);
+        }
+        let mut wilds = [false; 13];
+        for card in &wild {
+            wilds[*card as usize] = true;
+        }
+        RuleSet {
+            order,
+            wild,
+            hand_size,
+            kinds,
+            strengths,
+            wilds,
         }
+    }
 
-        let cfg = z3::Config::new();
-        let ctx = z3::Context::new(&cfg);
-        let opt = z3::Optimize::new(&ctx);
-
-        let two = Int::from_i64(&ctx, 2);
-        let one = Int::from_i64(&ctx, 1);
-        let zero = Int::from_i64(&ctx, 0);
-        let twelve = Int::from_i64(&ctx, 12);
-
-        let hand = (0..5)
-            .map(|idx| Int::new_const(&ctx, format!("h{}", idx).as_str()))
-            .collect::<Vec<Int>>();
-        for (var, card) in hand.iter().zip(self.cards) {
-            if card == Card::Jack {
-                opt.assert(&var.ge(&one));
-                opt.assert(&var.le(&twelve));
-            } else {
-                opt.assert(&var._eq(&Int::from_i64(&ctx, card.joker_value() as i64)));
+    fn standard_kinds() -> Vec<HandKind> {
+        vec![
+            HandKind::new("high card", &[1]),
+            HandKind::new("one pair", &[2]),
+            HandKind::new("two pair", &[2, 2]),
+            HandKind::new("three of a kind", &[3]),
+            HandKind::new("full house", &[3, 2]),
+            HandKind::new("four of a kind", &[4]),
+            HandKind::new("five of a kind", &[5]),
+        ]
+    }
+
+    fn standard() -> Self {
+        RuleSet::build(
+            Card::ALL.into_iter().rev().collect(),
+            Vec::new(),
+            5,
+            Self::standard_kinds(),
+        )
+    }
+
+    fn jokers() -> Self {
+        RuleSet::build(
+            [Card::Jack]
+                .into_iter()
+                .chain(
+                    Card::ALL
+                        .into_iter()
+                        .rev()
+                        .filter(|card| *card != Card::Jack),
+                )
+                .collect(),
+            vec![Card::Jack],
+            5,
+            Self::standard_kinds(),
+        )
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut order = None;
+        let mut wild = Vec::new();
+        let mut hand_size = None;
+        let mut kinds = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
             }
-        }
-        let totals: Vec<Int> = (1..=12)
-            .map(|value| {
-                let value = Int::from_i64(&ctx, value);
-                let mut total = zero.clone();
-                for var in hand.iter() {
-                    total = total.add(&var._eq(&value).ite(&one, &zero));
+            let (key, value) = line
+                .split_once(':')
+                .with_context(|| format!("line {}: expected \"key: value\"", line_no + 1))?;
+            let value = value.trim();
+            let cards = || {
+                value
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(Card::try_from)
+                    .collect::<Result<Vec<_>>>()
+            };
+            match key.trim() {
+                "order" => order = Some(cards()?),
+                "wild" => wild = cards()?,
+                "hand size" => {
+                    hand_size = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("line {}: bad hand size", line_no + 1))?,
+                    )
                 }
-                total
-            })
-            .collect();
+                "kind" => {
+                    let (name, groups) = value.rsplit_once('=').with_context(|| {
+                        format!("line {}: expected \"kind: name = groups\"", line_no + 1)
+                    })?;
+                    let groups = groups
+                        .split_ascii_whitespace()
+                        .map(|group| group.parse())
+                        .collect::<Result<Vec<usize>, _>>()
+                        .with_context(|| format!("line {}: bad kind groups", line_no + 1))?;
+                    kinds.push(HandKind::new(name.trim(), groups.as_slice()));
+                }
+                unexpected => {
+                    return Err(anyhow!("line {}: unknown key {unexpected:?}", line_no + 1))
+                }
+            }
+        }
+        RuleSet::new(
+            order.context("missing card order")?,
+            wild,
+            hand_size.context("missing hand size")?,
+            kinds,
+        )
+    }
 
-        let f = Bool::from_bool(&ctx, false);
+    fn load(path: &str) -> Result<Self> {
+        let mut text = String::new();
+        File::open(path)
+            .with_context(|| format!("error opening rules {path}"))?
+            .read_to_string(&mut text)
+            .with_context(|| format!("error reading rules {path}"))?;
+        RuleSet::parse(text.as_str()).with_context(|| format!("error parsing rules {path}"))
+    }
 
-        let five_of_a_kind = any_total(&ctx, totals.as_slice(), |total| {
-            total._eq(&Int::from_i64(&ctx, 5))
-        })
-        .ite(&Int::from_i64(&ctx, 1 << 6), &zero);
-        let four_of_a_kind = any_total(&ctx, totals.as_slice(), |total| {
-            total._eq(&Int::from_i64(&ctx, 4))
-        })
-        .ite(&Int::from_i64(&ctx, 1 << 5), &zero);
-        let full_house = any_total(&ctx, totals.as_slice(), |total| {
-            total._eq(&Int::from_i64(&ctx, 3))
-        })
-        .ite(
-            &any_total(&ctx, totals.as_slice(), |total| {
-                total._eq(&Int::from_i64(&ctx, 2))
-            }),
-            &f,
-        )
-        .ite(&Int::from_i64(&ctx, 1 << 4), &zero);
-        let three_of_a_kind = any_total(&ctx, totals.as_slice(), |total| {
-            total._eq(&Int::from_i64(&ctx, 3))
-        })
-        .ite(&Int::from_i64(&ctx, 1 << 3), &zero);
+    fn strength(&self, card: Card) -> Result<u32> {
+        self.strengths[card as usize]
+            .with_context(|| format!("{card:?} is not part of this rule set"))
+    }
 
-        let two_pair = {
-            let mut sum = zero.clone();
-            for total in &totals {
-                sum = sum.add(&total._eq(&two).ite(&one, &zero));
+    fn counts(&self, hand: &Hand) -> Result<(usize, [usize; 13])> {
+        if hand.cards.len() != self.hand_size {
+            return Err(anyhow!(
+                "{:?} has {} cards, expected {}",
+                hand.cards,
+                hand.cards.len(),
+                self.hand_size
+            ));
+        }
+        let mut wilds = 0;
+        let mut counts = [0; 13];
+        for card in hand.cards.iter().copied() {
+            if self.wilds[card as usize] {
+                wilds += 1;
+            } else {
+                counts[card as usize] += 1;
             }
-            sum._eq(&two)
         }
-        .ite(&Int::from_i64(&ctx, 1 << 2), &zero);
+        Ok((wilds, counts))
+    }
 
-        let pair = any_total(&ctx, totals.as_slice(), |total| {
-            total._eq(&Int::from_i64(&ctx, 2))
-        })
-        .ite(&Int::from_i64(&ctx, 1 << 1), &zero);
-
-        let score = five_of_a_kind
-            .add(&four_of_a_kind)
-            .add(&full_house)
-            .add(&three_of_a_kind)
-            .add(&two_pair)
-            .add(&pair);
-
-        opt.maximize(&score);
-        if opt.check(&[]) == SatResult::Unsat {
-            panic!();
+    fn groups(&self, counts: &[usize; 13]) -> Result<Vec<(usize, Card)>> {
+        let mut groups = Vec::new();
+        for (card, count) in Card::ALL.into_iter().zip(counts.iter().copied()) {
+            if count > 0 {
+                groups.push((count, card, self.strength(card)?));
+            }
         }
-        if let Some(ans) = opt.get_model() {
-            if let Some(res) = ans.eval(&score, true) {
-                if let Some(value) = res.as_i64() {
-                    if value >= 1 << 6 {
-                        return HandKind::FiveOfAKind;
-                    }
-                    if value >= 1 << 5 {
-                        return HandKind::FourOfAKind;
-                    }
-                    if value >= 1 << 4 {
-                        return HandKind::FullHouse;
-                    }
-                    if value >= 1 << 3 {
-                        return HandKind::ThreeOfAKind;
-                    }
-                    if value >= 1 << 2 {
-                        return HandKind::TwoPair;
-                    }
-                    if value >= 1 << 1 {
-                        return HandKind::OnePair;
-                    }
-                    return HandKind::HighCard;
+        groups.sort_unstable_by_key(|(count, _, strength)| Reverse((*count, *strength)));
+        Ok(groups
+            .into_iter()
+            .map(|(count, card, _)| (count, card))
+            .collect())
+    }
+
+    fn kind_of(&self, hand: &Hand, wilds: usize, counts: &[usize; 13]) -> Result<usize> {
+        let mut counts = *counts;
+        counts.sort_unstable_by_key(|count| Reverse(*count));
+        self.kinds
+            .iter()
+            .rposition(|kind| kind.wilds_needed(counts.as_slice()) <= wilds)
+            .with_context(|| format!("{:?} matches no hand kind", hand.cards))
+    }
+
+    fn kind(&self, hand: &Hand) -> Result<usize> {
+        let (wilds, counts) = self.counts(hand)?;
+        self.kind_of(hand, wilds, &counts)
+    }
+
+    fn substitute(&self, hand: &Hand, groups: &[(usize, Card)], kind: usize) -> Vec<Card> {
+        let mut fresh = self
+            .order
+            .iter()
+            .rev()
+            .copied()
+            .filter(|card| !self.wild.contains(card) && !hand.cards.contains(card));
+        let mut targets = Vec::new();
+        for (idx, group) in self.kinds[kind].groups.iter().copied().enumerate() {
+            let (count, card) = match groups.get(idx) {
+                Some((count, card)) => (*count, *card),
+                None => match fresh.next() {
+                    Some(card) => (0, card),
+                    None => break,
+                },
+            };
+            targets.extend(std::iter::repeat_n(card, group.saturating_sub(count)));
+        }
+        let leftover = targets
+            .first()
+            .copied()
+            .or(groups.first().map(|(_, card)| *card));
+        let mut targets = targets.into_iter();
+        hand.cards
+            .iter()
+            .copied()
+            .map(|card| {
+                if self.wilds[card as usize] {
+                    targets.next().or(leftover).unwrap_or(card)
+                } else {
+                    card
                 }
-            }
+            })
+            .collect()
+    }
+
+    fn key(&self, hand: &Hand) -> Result<u32> {
+        let card_bits = bits_for(self.order.len());
+        let mut key = self.kind(hand)? as u32;
+        for card in hand.cards.iter().copied() {
+            key = key << card_bits | self.strength(card)?;
         }
+        Ok(key)
+    }
+}
 
-        panic!()
+impl Hand {
+    fn key(&self, rules: &RuleSet) -> Result<u32> {
+        rules
+            .key(self)
+            .with_context(|| format!("error ranking hand {:?}", self.cards))
     }
 }
 
-impl Ord for Hand {
+#[derive(Debug)]
+struct Ranked<'a> {
+    key: u32,
+    hand: &'a Hand,
+}
+
+impl Ord for Ranked<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.kind()
-            .cmp(&other.kind())
-            .then_with(|| self.cards.cmp(&other.cards))
+        self.key.cmp(&other.key)
     }
 }
 
-impl PartialOrd for Hand {
+impl PartialOrd for Ranked<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-fn parse_hands() -> Result<Vec<Hand>> {
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+#[cfg(feature = "z3")]
+fn z3_joker_kind(cards: &[Card]) -> Result<usize> {
+    fn any_total<'a>(
+        ctx: &'a z3::Context,
+        totals: &'a [Int<'a>],
+        cond: impl Fn(&'a Int<'a>) -> Bool<'a>,
+    ) -> Bool<'a> {
+        let f = Bool::from_bool(ctx, false);
+        let mut any = f.clone();
+        for total in totals {
+            let res = cond(total);
+            any = any._eq(&f).ite(&res._eq(&f), &f).not();
+        }
+        any
+    }
+
+    let cfg = z3::Config::new();
+    let ctx = z3::Context::new(&cfg);
+    let opt = z3::Optimize::new(&ctx);
+
+    let two = Int::from_i64(&ctx, 2);
+    let one = Int::from_i64(&ctx, 1);
+    let zero = Int::from_i64(&ctx, 0);
+    let twelve = Int::from_i64(&ctx, 12);
+
+    let hand = (0..cards.len())
+        .map(|idx| Int::new_const(&ctx, format!("h{}", idx).as_str()))
+        .collect::<Vec<Int>>();
+    for (var, card) in hand.iter().zip(cards.iter().copied()) {
+        if card == Card::Jack {
+            opt.assert(&var.ge(&one));
+            opt.assert(&var.le(&twelve));
+        } else {
+            opt.assert(&var._eq(&Int::from_i64(&ctx, card.joker_value() as i64)));
+        }
+    }
+    let totals: Vec<Int> = (1..=12)
+        .map(|value| {
+            let value = Int::from_i64(&ctx, value);
+            let mut total = zero.clone();
+            for var in hand.iter() {
+                total = total.add(&var._eq(&value).ite(&one, &zero));
+            }
+            total
+        })
+        .collect();
+
+    let f = Bool::from_bool(&ctx, false);
+
+    let five_of_a_kind = any_total(&ctx, totals.as_slice(), |total| {
+        total._eq(&Int::from_i64(&ctx, 5))
+    })
+    .ite(&Int::from_i64(&ctx, 1 << 6), &zero);
+    let four_of_a_kind = any_total(&ctx, totals.as_slice(), |total| {
+        total._eq(&Int::from_i64(&ctx, 4))
+    })
+    .ite(&Int::from_i64(&ctx, 1 << 5), &zero);
+    let full_house = any_total(&ctx, totals.as_slice(), |total| {
+        total._eq(&Int::from_i64(&ctx, 3))
+    })
+    .ite(
+        &any_total(&ctx, totals.as_slice(), |total| {
+            total._eq(&Int::from_i64(&ctx, 2))
+        }),
+        &f,
+    )
+    .ite(&Int::from_i64(&ctx, 1 << 4), &zero);
+    let three_of_a_kind = any_total(&ctx, totals.as_slice(), |total| {
+        total._eq(&Int::from_i64(&ctx, 3))
+    })
+    .ite(&Int::from_i64(&ctx, 1 << 3), &zero);
+
+    let two_pair = {
+        let mut sum = zero.clone();
+        for total in &totals {
+            sum = sum.add(&total._eq(&two).ite(&one, &zero));
+        }
+        sum._eq(&two)
+    }
+    .ite(&Int::from_i64(&ctx, 1 << 2), &zero);
+
+    let pair = any_total(&ctx, totals.as_slice(), |total| {
+        total._eq(&Int::from_i64(&ctx, 2))
+    })
+    .ite(&Int::from_i64(&ctx, 1 << 1), &zero);
+
+    let score = five_of_a_kind
+        .add(&four_of_a_kind)
+        .add(&full_house)
+        .add(&three_of_a_kind)
+        .add(&two_pair)
+        .add(&pair);
+
+    opt.maximize(&score);
+    if opt.check(&[]) == SatResult::Unsat {
+        return Err(anyhow!("z3 found no assignment for {cards:?}"));
+    }
+    let value = opt
+        .get_model()
+        .with_context(|| format!("z3 returned no model for {cards:?}"))?
+        .eval(&score, true)
+        .and_then(|res| res.as_i64())
+        .with_context(|| format!("z3 could not evaluate the score of {cards:?}"))?;
+    Ok((1..=6).rev().find(|kind| value >= 1 << kind).unwrap_or(0))
+}
+
+fn parse_hands(path: &str) -> Result<Vec<Hand>> {
     let mut hands = Vec::new();
     for result in
-        BufReader::new(File::open("day7/input.txt").context("error opening input")?).lines()
+        BufReader::new(File::open(path).with_context(|| format!("error opening input {path}"))?)
+            .lines()
     {
         let line = result.context("error reading input")?;
-        let (cards_text, bid_text) = line.split_at(5);
-        let mut cards = [Card::Ace; 5];
-        for (i, card) in cards_text.chars().enumerate() {
-            cards[i] = match card {
-                'A' => Card::Ace,
-                'K' => Card::King,
-                'Q' => Card::Queen,
-                'J' => Card::Jack,
-                'T' => Card::Ten,
-                '9' => Card::Nine,
-                '8' => Card::Eight,
-                '7' => Card::Seven,
-                '6' => Card::Six,
-                '5' => Card::Five,
-                '4' => Card::Four,
-                '3' => Card::Three,
-                '2' => Card::Two,
-                unexpected => return Err(anyhow!("unknown card {unexpected:?}")),
-            }
-        }
+        let (cards_text, bid_text) = line
+            .trim()
+            .split_once(' ')
+            .with_context(|| format!("expected cards and bid in {line:?}"))?;
+        let cards = cards_text
+            .chars()
+            .map(Card::try_from)
+            .collect::<Result<Vec<_>>>()?;
         let bid = bid_text.trim().parse().context("error parsing bid")?;
         hands.push(Hand { cards, bid })
     }
     Ok(hands)
 }
 
+#[cfg(feature = "z3")]
+fn check_joker_kinds() -> Result<()> {
+    let rules = RuleSet::jokers();
+    // Hand kinds ignore card order, so walking every multiset of five cards
+    // (non-decreasing indices) covers every ordered hand.
+    let mut idx = [0; 5];
+    loop {
+        let hand = Hand {
+            cards: idx.iter().map(|i| Card::ALL[*i]).collect(),
+            bid: 0,
+        };
+        let (direct, z3) = (rules.kind(&hand)?, z3_joker_kind(hand.cards.as_slice())?);
+        if direct != z3 {
+            return Err(anyhow!(
+                "{:?}: direct evaluator gave {:?}, z3 gave {:?}",
+                hand.cards,
+                rules.kinds[direct].name,
+                rules.kinds[z3].name
+            ));
+        }
+
+        let Some(pos) = idx.iter().rposition(|i| *i + 1 < Card::ALL.len()) else {
+            return Ok(());
+        };
+        let next = idx[pos] + 1;
+        idx[pos..].fill(next);
+    }
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
 fn main() -> Result<()> {
-    let hands = parse_hands()?;
+    #[cfg(feature = "z3")]
+    if std::env::args().any(|arg| arg == "--check-z3") {
+        check_joker_kinds()?;
+        println!("z3 and direct joker evaluators agree");
+    }
+
+    let hands_path = arg_value("--hands");
+    let hands = parse_hands(hands_path.as_deref().unwrap_or("day7/input.txt"))?;
+    let rules_path = arg_value("--rules");
+    if std::env::args().any(|arg| arg == "--explain") {
+        let rules = match rules_path.as_deref() {
+            Some(path) => RuleSet::load(path)?,
+            None => RuleSet::jokers(),
+        };
+        return explain(hands.as_slice(), &rules);
+    }
+    if let Some(rules_path) = rules_path {
+        let rules = RuleSet::load(rules_path.as_str())?;
+        println!(
+            "{rules_path}: {}",
+            winnings(rank(hands.as_slice(), &rules)?.as_slice())
+        );
+        return Ok(());
+    }
     println!("part1: {}", part1(hands.as_slice())?);
     println!("part2: {}", part2(hands.as_slice())?);
     Ok(())
 }
 
-fn part1(hands: &[Hand]) -> Result<usize> {
-    let mut hands = hands.to_vec();
-    hands.sort();
-    hands.reverse();
-    Ok(hands
-        .into_iter()
-        .enumerate()
-        .map(|(pos, hand)| hand.bid * (1 + pos))
-        .sum())
+fn explain(hands: &[Hand], rules: &RuleSet) -> Result<()> {
+    let ranked = rank(hands, rules)?;
+    let width = rules.hand_size.max(4);
+    println!(
+        "{:>5}  {:<width$}  {:<width$}  {:<16}  {:>6}  {:>10}",
+        "rank", "hand", "as", "kind", "bid", "winnings"
+    );
+    let mut total = 0;
+    for (pos, Ranked { hand, .. }) in ranked.iter().enumerate() {
+        let rank = pos + 1;
+        let (wilds, counts) = rules.counts(hand)?;
+        let kind = rules.kind_of(hand, wilds, &counts)?;
+        let groups = rules.groups(&counts)?;
+        let cards = hand.cards.iter().map(Card::to_string).collect::<String>();
+        let substituted = rules
+            .substitute(hand, groups.as_slice(), kind)
+            .iter()
+            .map(Card::to_string)
+            .collect::<String>();
+        let kind = &rules.kinds[kind].name;
+        total += hand.bid * rank;
+        println!(
+            "{rank:>5}  {cards:<width$}  {substituted:<width$}  {kind:<16}  {:>6}  {:>10}",
+            hand.bid,
+            hand.bid * rank
+        );
+    }
+    println!("total: {total}");
+    Ok(())
 }
 
-fn part2(hands: &[Hand]) -> Result<usize> {
-    let mut hands = hands
+fn rank<'a>(hands: &'a [Hand], rules: &RuleSet) -> Result<Vec<Ranked<'a>>> {
+    let mut ranked = hands
         .iter()
         .map(|hand| {
-            (
-                (hand.joker_kind(), hand.cards.map(|card| card.joker_value())),
+            Ok(Ranked {
+                key: hand.key(rules)?,
                 hand,
-            )
+            })
         })
-        .collect::<Vec<_>>();
-    hands.sort_by_key(|(key, _)| *key);
-    hands.reverse();
-    Ok(hands
-        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    ranked.sort_unstable();
+    Ok(ranked)
+}
+
+fn winnings(ranked: &[Ranked]) -> usize {
+    ranked
+        .iter()
         .enumerate()
-        .map(|(pos, (_, hand))| hand.bid * (1 + pos))
-        .sum())
+        .map(|(pos, Ranked { hand, .. })| hand.bid * (1 + pos))
+        .sum()
+}
+
+fn part1(hands: &[Hand]) -> Result<usize> {
+    Ok(winnings(rank(hands, &RuleSet::standard())?.as_slice()))
+}
+
+fn part2(hands: &[Hand]) -> Result<usize> {
+    Ok(winnings(rank(hands, &RuleSet::jokers())?.as_slice()))
+}
+
+#[cfg(all(test, feature = "z3"))]
+mod tests {
+    #[test]
+    fn z3_agrees_with_direct_evaluator() -> anyhow::Result<()> {
+        super::check_joker_kinds()
+    }
 }
@@ -1,38 +1,34 @@
 use anyhow::{anyhow, Context, Result};
-use std::collections::HashMap;
-use std::fmt;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
-struct Node([u8; 3]);
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Debug)]
+struct Node(u32);
 
-impl fmt::Display for Node {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}{}{}",
-            self.0[0] as char, self.0[1] as char, self.0[2] as char
-        )
-    }
+#[derive(Default)]
+struct Interner {
+    names: Vec<String>,
+    ids: HashMap<String, Node>,
 }
 
-impl fmt::Debug for Node {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{self}")
+impl Interner {
+    fn intern(&mut self, name: &str) -> Node {
+        if let Some(node) = self.ids.get(name) {
+            return *node;
+        }
+        let node = Node(self.names.len() as u32);
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), node);
+        node
     }
-}
 
-impl<'a> TryFrom<&'a str> for Node {
-    type Error = anyhow::Error;
+    fn get(&self, name: &str) -> Option<Node> {
+        self.ids.get(name).copied()
+    }
 
-    fn try_from(value: &'a str) -> Result<Self> {
-        if value.len() == 3 {
-            let bytes = value.as_bytes();
-            Ok(Node([bytes[0], bytes[1], bytes[2]]))
-        } else {
-            Err(anyhow!("bad node: {value}"))
-        }
+    fn name(&self, node: Node) -> &str {
+        self.names[node.0 as usize].as_str()
     }
 }
 
@@ -44,107 +40,481 @@ enum Direction {
 
 struct Network {
     instructions: Vec<Direction>,
-    nodes: HashMap<Node, (Node, Node)>,
+    names: Interner,
+    nodes: Vec<Option<(Node, Node)>>,
+}
+
+impl Network {
+    fn name(&self, node: Node) -> &str {
+        self.names.name(node)
+    }
+
+    fn node(&self, name: &str) -> Result<Node> {
+        self.names
+            .get(name)
+            .with_context(|| format!("unknown node {name}"))
+    }
+
+    fn defined_nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, edges)| edges.is_some())
+            .map(|(id, _)| Node(id as u32))
+    }
+
+    fn step(&self, node: Node, direction: Direction) -> Result<Node> {
+        let (left, right) = self.nodes[node.0 as usize]
+            .with_context(|| format!("node {} has no connections", self.name(node)))?;
+        Ok(match direction {
+            Direction::Left => left,
+            Direction::Right => right,
+        })
+    }
+}
+
+fn parse_node_name(name: &str, line_no: usize) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(anyhow!("line {line_no}: bad node name {name:?}"));
+    }
+    Ok(name)
 }
 
 fn parse_network() -> Result<Network> {
+    read_network(BufReader::new(
+        File::open("./day8/input.txt").context("error opening input")?,
+    ))
+}
+
+fn read_network(reader: impl BufRead) -> Result<Network> {
     let mut network = Network {
         instructions: Vec::new(),
-        nodes: HashMap::new(),
+        names: Interner::default(),
+        nodes: Vec::new(),
     };
-    for (line_no, result) in
-        BufReader::new(File::open("./day8/input.txt").context("error opening input")?)
-            .lines()
-            .enumerate()
-    {
+    for (idx, result) in reader.lines().enumerate() {
+        let line_no = idx + 1;
         let line = result.context("error reading input")?;
-        match line_no {
-            0 => {
-                for c in line.trim().chars() {
-                    match c {
-                        'L' => network.instructions.push(Direction::Left),
-                        'R' => network.instructions.push(Direction::Right),
-                        _ => return Err(anyhow!("unknown direction: {c}")),
-                    }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if network.instructions.is_empty() {
+            for c in line.chars() {
+                match c {
+                    'L' => network.instructions.push(Direction::Left),
+                    'R' => network.instructions.push(Direction::Right),
+                    _ => return Err(anyhow!("line {line_no}: unknown direction: {c}")),
                 }
             }
-            1 => {}
-            _ => {
-                let (src, rest) = line.split_at(3);
-                let (_, rest) = rest.split_at(4);
-                let (left, rest) = rest.split_at(3);
-                let (_, rest) = rest.split_at(2);
-                let (right, _) = rest.split_at(3);
-                network
-                    .nodes
-                    .insert(src.try_into()?, (left.try_into()?, right.try_into()?));
-            }
+            continue;
         }
+
+        let (src, rest) = line
+            .split_once('=')
+            .with_context(|| format!("line {line_no}: expected \"NAME = (LEFT, RIGHT)\""))?;
+        let (left, right) = rest
+            .trim()
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|rest| rest.split_once(','))
+            .with_context(|| format!("line {line_no}: expected \"(LEFT, RIGHT)\""))?;
+        let src = network.names.intern(parse_node_name(src, line_no)?);
+        let left = network.names.intern(parse_node_name(left, line_no)?);
+        let right = network.names.intern(parse_node_name(right, line_no)?);
+        network.nodes.resize(network.names.names.len(), None);
+        if network.nodes[src.0 as usize]
+            .replace((left, right))
+            .is_some()
+        {
+            return Err(anyhow!(
+                "line {line_no}: node {} defined twice",
+                network.name(src)
+            ));
+        }
+    }
+    if network.instructions.is_empty() {
+        return Err(anyhow!("no instructions found"));
     }
     Ok(network)
 }
 
+fn edges(network: &Network, node: Node) -> Vec<(Node, &'static str)> {
+    match network.nodes[node.0 as usize] {
+        Some((left, right)) if left == right => vec![(left, "L/R")],
+        Some((left, right)) => vec![(left, "L"), (right, "R")],
+        None => Vec::new(),
+    }
+}
+
+fn reachable(network: &Network, start: Node) -> Vec<Node> {
+    let mut seen = vec![false; network.nodes.len()];
+    let mut stack = vec![start];
+    let mut result = Vec::new();
+    seen[start.0 as usize] = true;
+    while let Some(node) = stack.pop() {
+        result.push(node);
+        for (next, _) in edges(network, node) {
+            if !std::mem::replace(&mut seen[next.0 as usize], true) {
+                stack.push(next);
+            }
+        }
+    }
+    result
+}
+
+fn dot_escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_dot(network: &Network, collapse: bool, out: &mut impl Write) -> io::Result<()> {
+    let is_start = |node: Node| network.name(node).ends_with('A');
+    let is_end = |node: Node| network.name(node).ends_with('Z');
+    let starts = network
+        .defined_nodes()
+        .filter(|node| is_start(*node))
+        .collect::<Vec<_>>();
+
+    let mut owner = vec![None; network.nodes.len()];
+    if collapse {
+        for start in starts.iter().copied() {
+            for node in reachable(network, start) {
+                owner[node.0 as usize].get_or_insert(start);
+            }
+        }
+    }
+    let representative = |node: Node| owner[node.0 as usize].unwrap_or(node);
+
+    writeln!(out, "digraph network {{")?;
+    for start in starts.iter().copied() {
+        if !collapse || owner[start.0 as usize] != Some(start) {
+            continue;
+        }
+        let members = (0..network.nodes.len())
+            .filter(|id| owner[*id] == Some(start))
+            .map(|id| Node(id as u32))
+            .collect::<Vec<_>>();
+        let ends = members
+            .iter()
+            .filter(|node| is_end(**node))
+            .map(|node| network.name(*node))
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "    \"{}\" [shape=box, style=filled, fillcolor=palegreen, label=\"{}\\n{} nodes\\nends: {}\"];",
+            dot_escape(network.name(start)),
+            dot_escape(network.name(start)),
+            members.len(),
+            dot_escape(ends.join(", ").as_str())
+        )?;
+    }
+    for (id, owned) in owner.iter().enumerate() {
+        let node = Node(id as u32);
+        if owned.is_some() {
+            continue;
+        }
+        let style = if is_start(node) {
+            " [style=filled, fillcolor=palegreen]"
+        } else if is_end(node) {
+            " [style=filled, fillcolor=lightcoral]"
+        } else {
+            ""
+        };
+        writeln!(out, "    \"{}\"{style};", dot_escape(network.name(node)))?;
+    }
+
+    let mut written = HashSet::new();
+    for (id, owned) in owner.iter().enumerate() {
+        let node = Node(id as u32);
+        let from = representative(node);
+        for (next, label) in edges(network, node) {
+            let to = representative(next);
+            if owned.is_some() && *owned == owner[next.0 as usize] {
+                continue;
+            }
+            if written.insert((from, to, label)) {
+                writeln!(
+                    out,
+                    "    \"{}\" -> \"{}\" [label=\"{label}\"];",
+                    dot_escape(network.name(from)),
+                    dot_escape(network.name(to))
+                )?;
+            }
+        }
+    }
+    writeln!(out, "}}")
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
 fn main() -> Result<()> {
     let network = parse_network()?;
-    println!("part1: {}", part1(&network)?);
+    if let Some(path) = arg_value("--dot") {
+        let collapse = std::env::args().any(|arg| arg == "--collapse");
+        let mut out =
+            BufWriter::new(File::create(path.as_str()).context("error creating dot file")?);
+        write_dot(&network, collapse, &mut out).context("error writing dot file")?;
+        out.flush().context("error writing dot file")?;
+        return Ok(());
+    }
+    let max_steps = arg_value("--max-steps")
+        .map(|value| value.parse().context("error parsing --max-steps"))
+        .transpose()?;
+    println!("part1: {}", part1(&network, max_steps)?);
     println!("part2: {}", part2(&network)?);
     Ok(())
 }
 
-fn part1(network: &Network) -> Result<usize> {
-    get_steps(network, Node([b'A', b'A', b'A']), |node| {
-        node == Node([b'Z', b'Z', b'Z'])
-    })
+fn part1(network: &Network, max_steps: Option<usize>) -> Result<usize> {
+    let end = network.node("ZZZ")?;
+    get_steps(network, network.node("AAA")?, |node| node == end, max_steps)
 }
 
-fn get_steps(network: &Network, mut node: Node, end_cond: impl Fn(Node) -> bool) -> Result<usize> {
-    let mut next_dir = network.instructions.iter().cycle();
+fn get_steps(
+    network: &Network,
+    start: Node,
+    end_cond: impl Fn(Node) -> bool,
+    max_steps: Option<usize>,
+) -> Result<usize> {
+    let states = network.instructions.len();
+    let mut seen = vec![false; network.nodes.len() * states];
+    let mut node = start;
     let mut steps = 0;
     loop {
         if end_cond(node) {
             return Ok(steps);
         }
+        if max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            return Err(anyhow!(
+                "goal not reached from {} within {steps} steps",
+                network.name(start)
+            ));
+        }
 
-        let dir = next_dir.next().context("error getting next instruction")?;
-        let (left, right) = network
-            .nodes
-            .get(&node)
-            .context("error finding current node")?;
-        match dir {
-            Direction::Left => node = *left,
-            Direction::Right => node = *right,
+        let instruction = steps % states;
+        if std::mem::replace(&mut seen[node.0 as usize * states + instruction], true) {
+            return Err(anyhow!("goal unreachable from {}", network.name(start)));
         }
+        node = network.step(node, network.instructions[instruction])?;
 
         steps += 1;
     }
 }
 
+const MAX_RESIDUES: usize = 1 << 20;
+
+struct Cycle {
+    start: usize,
+    len: usize,
+    pre_cycle_hits: Vec<usize>,
+    cycle_hits: Vec<usize>,
+}
+
+impl Cycle {
+    fn is_hit(&self, step: usize) -> bool {
+        if step < self.start {
+            self.pre_cycle_hits.contains(&step)
+        } else {
+            self.cycle_hits
+                .iter()
+                .any(|hit| hit % self.len == step % self.len)
+        }
+    }
+}
+
+fn find_cycle(network: &Network, mut node: Node, end_cond: impl Fn(Node) -> bool) -> Result<Cycle> {
+    let states = network.instructions.len();
+    let mut seen = vec![None; network.nodes.len() * states];
+    let mut hits = Vec::new();
+    let mut step = 0;
+    loop {
+        let instruction = step % states;
+        if let Some(start) = seen[node.0 as usize * states + instruction].replace(step) {
+            let (pre_cycle_hits, cycle_hits) = hits.into_iter().partition(|hit| *hit < start);
+            return Ok(Cycle {
+                start,
+                len: step - start,
+                pre_cycle_hits,
+                cycle_hits,
+            });
+        }
+        if end_cond(node) {
+            hits.push(step);
+        }
+
+        node = network.step(node, network.instructions[instruction])?;
+
+        step += 1;
+    }
+}
+
 fn part2(network: &Network) -> Result<usize> {
-    let nodes: Vec<Node> = network
-        .nodes
-        .keys()
-        .filter(|node| node.0[2] == b'A')
-        .copied()
-        .collect();
-    let steps = nodes
-        .iter()
-        .copied()
-        .map(|node| get_steps(network, node, |node| node.0[2] == b'Z'))
+    let is_end = |node: Node| network.name(node).ends_with('Z');
+    let cycles = network
+        .defined_nodes()
+        .filter(|node| network.name(*node).ends_with('A'))
+        .map(|node| find_cycle(network, node, is_end))
         .collect::<Result<Vec<_>>>()?;
-    steps.into_iter().reduce(lcm).context("no steps")
+    let latest_start = cycles
+        .iter()
+        .map(|cycle| cycle.start)
+        .max()
+        .context("no ghosts")?;
+
+    let early = cycles
+        .iter()
+        .flat_map(|cycle| cycle.pre_cycle_hits.iter().copied())
+        .filter(|step| cycles.iter().all(|cycle| cycle.is_hit(*step)))
+        .min();
+    if let Some(step) = early {
+        return Ok(step);
+    }
+
+    // Every compatible pairing of end offsets survives as a residue, so the
+    // count can grow as the product of each ghost's hits per cycle.
+    let mut residues = vec![0i128];
+    let mut modulus = 1i128;
+    for cycle in &cycles {
+        let len = cycle.len as i128;
+        let mut combined = Vec::new();
+        let mut combined_modulus = modulus;
+        for a in residues.iter().copied() {
+            for hit in cycle.cycle_hits.iter() {
+                if let Some((residue, new_modulus)) = crt(a, modulus, *hit as i128 % len, len) {
+                    combined.push(residue);
+                    combined_modulus = new_modulus;
+                }
+            }
+        }
+        combined.sort_unstable();
+        combined.dedup();
+        if combined.len() > MAX_RESIDUES {
+            return Err(anyhow!(
+                "ghost end offsets combine into more than {MAX_RESIDUES} residues"
+            ));
+        }
+        residues = combined;
+        modulus = combined_modulus;
+    }
+
+    let latest_start = latest_start as i128;
+    residues
+        .into_iter()
+        .map(|residue| {
+            if residue >= latest_start {
+                residue
+            } else {
+                residue + (latest_start - residue + modulus - 1) / modulus * modulus
+            }
+        })
+        .min()
+        .context("ghosts never all reach an end node at once")?
+        .try_into()
+        .context("step count overflowed")
 }
 
-fn lcm(a: usize, b: usize) -> usize {
-    a * b / gcd(a, b)
+fn crt(a1: i128, m1: i128, a2: i128, m2: i128) -> Option<(i128, i128)> {
+    let (g, p, _) = extended_gcd(m1, m2);
+    if (a2 - a1) % g != 0 {
+        return None;
+    }
+    let lcm = m1 / g * m2;
+    let k = ((a2 - a1) / g % (m2 / g)) * (p % (m2 / g)) % (m2 / g);
+    Some(((a1 + m1 * k).rem_euclid(lcm), lcm))
 }
 
-fn gcd(mut a: usize, mut b: usize) -> usize {
-    loop {
-        if b == 0 {
-            return a;
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - a / b * y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{part2, read_network, Network};
+    use anyhow::Result;
+
+    fn network(nodes: &[(&str, &str)]) -> Result<Network> {
+        let mut text = String::from("L\n\n");
+        for (node, next) in nodes {
+            text += &format!("{node} = ({next}, {next})\n");
+        }
+        read_network(text.as_bytes())
+    }
+
+    fn brute_force(network: &Network, limit: usize) -> Option<usize> {
+        let mut ghosts = network
+            .defined_nodes()
+            .filter(|node| network.name(*node).ends_with('A'))
+            .collect::<Vec<_>>();
+        for step in 0..limit {
+            if ghosts.iter().all(|node| network.name(*node).ends_with('Z')) {
+                return Some(step);
+            }
+            for ghost in ghosts.iter_mut() {
+                *ghost = network.step(*ghost, network.instructions[0]).ok()?;
+            }
         }
-        let tmp = b;
-        b = a % b;
-        a = tmp;
+        None
+    }
+
+    #[test]
+    fn pre_cycle_hit() -> Result<()> {
+        let network = network(&[
+            ("1A", "1B"),
+            ("1B", "1C"),
+            ("1C", "1D"),
+            ("1D", "1E"),
+            ("1E", "1Z"),
+            ("1Z", "1F"),
+            ("1F", "1F"),
+            ("2A", "2B"),
+            ("2B", "2C"),
+            ("2C", "2Z"),
+            ("2Z", "2C"),
+        ])?;
+        assert_eq!(part2(&network)?, 5);
+        assert_eq!(brute_force(&network, 100), Some(5));
+        Ok(())
+    }
+
+    #[test]
+    fn several_hits_per_cycle() -> Result<()> {
+        let network = network(&[
+            ("1A", "1PZ"),
+            ("1PZ", "1B"),
+            ("1B", "1QZ"),
+            ("1QZ", "1C"),
+            ("1C", "1A"),
+            ("2A", "2B"),
+            ("2B", "2Z"),
+            ("2Z", "2A"),
+            ("3A", "3B"),
+            ("3B", "3Z"),
+            ("3Z", "3B"),
+        ])?;
+        assert_eq!(part2(&network)?, 8);
+        assert_eq!(brute_force(&network, 1000), Some(8));
+        Ok(())
+    }
+
+    #[test]
+    fn incompatible_residues() -> Result<()> {
+        let network = network(&[
+            ("1A", "1Z"),
+            ("1Z", "1A"),
+            ("2A", "2B"),
+            ("2B", "2Z"),
+            ("2Z", "2B"),
+        ])?;
+        assert!(part2(&network).is_err());
+        assert_eq!(brute_force(&network, 1000), None);
+        Ok(())
     }
 }
@@ -1,96 +1,389 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use num_bigint::BigInt;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
-fn parse_readings() -> Result<Vec<Vec<i64>>> {
-    let mut rows = Vec::new();
-    for result in
-        BufReader::new(File::open("./day9/input.txt").context("error opening input")?).lines()
-    {
-        rows.push(
-            result
-                .context("error reading from file")?
-                .split(' ')
-                .map(|n| n.parse().context("error parsing number"))
-                .collect::<Result<_>>()?,
-        )
+const BATCH_SIZE: usize = 1024;
+
+struct Polynomial<'a> {
+    len: usize,
+    leading: &'a [BigInt],
+    converged: bool,
+}
+
+impl<'a> Polynomial<'a> {
+    fn fit_in_place(diffs: &'a mut [BigInt]) -> Self {
+        let len = diffs.len();
+        let mut settled = len;
+        for k in 0..len {
+            if diffs[k..].iter().all(|n| *n == BigInt::ZERO) {
+                settled = k;
+                break;
+            }
+            for i in (k + 1..len).rev() {
+                let (before, after) = diffs.split_at_mut(i);
+                after[0] -= &before[i - 1];
+            }
+        }
+        Polynomial {
+            len,
+            leading: &diffs[..settled],
+            converged: settled < len || len == 0,
+        }
+    }
+
+    fn degree(&self) -> Option<usize> {
+        self.leading.len().checked_sub(1)
+    }
+
+    fn check(&self, max_degree: Option<usize>) -> Result<()> {
+        if !self.converged {
+            return Err(anyhow!(
+                "differences never settle within {} readings",
+                self.len
+            ));
+        }
+        match (self.degree(), max_degree) {
+            (Some(degree), Some(max_degree)) if degree > max_degree => {
+                Err(anyhow!("degree {degree} exceeds maximum of {max_degree}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn truncated(&self, degree: usize) -> Polynomial<'a> {
+        Polynomial {
+            len: self.len,
+            leading: &self.leading[..usize::min(degree + 1, self.leading.len())],
+            converged: true,
+        }
+    }
+
+    fn residuals(&self, nums: &[i64], degree: usize) -> Vec<BigInt> {
+        let fitted = self.truncated(degree);
+        nums.iter()
+            .enumerate()
+            .map(|(idx, n)| BigInt::from(*n) - fitted.at(idx as i64))
+            .collect()
+    }
+
+    fn at(&self, index: i64) -> BigInt {
+        let index = BigInt::from(index);
+        let mut binomial = BigInt::from(1);
+        let mut total = BigInt::ZERO;
+        for (k, difference) in self.leading.iter().enumerate() {
+            if k > 0 {
+                binomial = binomial * (&index - (k - 1)) / k;
+            }
+            total += &binomial * difference;
+        }
+        total
+    }
+
+    fn ahead(&self, steps: i64) -> BigInt {
+        self.at(self.len as i64 - 1 + steps)
+    }
+
+    fn behind(&self, steps: i64) -> BigInt {
+        self.at(-steps)
     }
-    Ok(rows)
 }
 
-fn main() -> Result<()> {
-    let readings = parse_readings()?;
-    println!("part1: {}", part1(readings.as_slice())?);
-    println!("part2: {}", part2(readings.as_slice())?);
-    Ok(())
+#[derive(Copy, Clone)]
+struct Options {
+    max_degree: Option<usize>,
+    at: Option<i64>,
+    show_degrees: bool,
+    show_residuals: bool,
 }
 
-fn part1(readings: &[Vec<i64>]) -> Result<i64> {
-    Ok(readings
-        .iter()
-        .map(|reading| {
-            predict(
-                reading.as_slice(),
-                |line| {
-                    line.last()
-                        .copied()
-                        .context("error getting last element of line")
-                },
-                |a, b| a + b,
-            )
-        })
-        .collect::<Result<Vec<i64>>>()?
-        .into_iter()
-        .sum())
+enum Outcome {
+    Fitted {
+        degree: Option<usize>,
+        next: BigInt,
+        previous: BigInt,
+        at: Option<BigInt>,
+    },
+    Skipped {
+        degree: Option<usize>,
+        reason: anyhow::Error,
+        residuals: Option<(usize, Vec<BigInt>)>,
+    },
 }
 
-fn part2(readings: &[Vec<i64>]) -> Result<i64> {
-    Ok(readings
-        .iter()
-        .map(|reading| {
-            predict(
-                reading.as_slice(),
-                |line| {
-                    line.first()
-                        .copied()
-                        .context("error getting first element of line")
-                },
-                |a, b| a - b,
-            )
+#[derive(Default)]
+struct Worker {
+    nums: Vec<i64>,
+    diffs: Vec<BigInt>,
+}
+
+impl Worker {
+    fn process(&mut self, line: &str, options: Options) -> Result<Outcome> {
+        self.nums.clear();
+        for n in line.split_ascii_whitespace() {
+            self.nums
+                .push(n.parse().with_context(|| format!("error parsing {n:?}"))?);
+        }
+        self.diffs.clear();
+        self.diffs
+            .extend(self.nums.iter().map(|n| BigInt::from(*n)));
+
+        let fit = Polynomial::fit_in_place(self.diffs.as_mut_slice());
+        let degree = if fit.converged { fit.degree() } else { None };
+        if let Err(reason) = fit.check(options.max_degree) {
+            let residuals = options.show_residuals.then(|| {
+                let degree = options
+                    .max_degree
+                    .unwrap_or(self.nums.len().saturating_sub(2));
+                (degree, fit.residuals(self.nums.as_slice(), degree))
+            });
+            return Ok(Outcome::Skipped {
+                degree,
+                reason,
+                residuals,
+            });
+        }
+        Ok(Outcome::Fitted {
+            degree,
+            next: fit.ahead(1),
+            previous: fit.behind(1),
+            at: options.at.map(|index| fit.at(index)),
         })
-        .collect::<Result<Vec<i64>>>()?
-        .into_iter()
-        .sum())
+    }
+}
+
+#[derive(Default)]
+struct Totals {
+    next: BigInt,
+    previous: BigInt,
+    at: BigInt,
 }
 
-fn predict(
-    nums: &[i64],
-    get: impl Fn(&Vec<i64>) -> Result<i64>,
-    op: impl Fn(i64, i64) -> i64,
-) -> Result<i64> {
-    let mut stack = Vec::<Vec<i64>>::new();
-    stack.push(nums.to_vec());
-    while !stack
-        .last()
-        .context("logic error")?
-        .iter()
-        .copied()
-        .all(|n| n == 0)
-    {
-        let prev = stack.last().context("logic error")?;
-        stack.push(
-            prev.iter()
-                .copied()
-                .skip(1)
-                .zip(prev.iter().copied())
-                .map(|(a, b)| a - b)
-                .collect(),
-        );
+impl Totals {
+    fn record(&mut self, row: usize, outcome: Outcome, options: Options) {
+        if options.show_degrees {
+            match &outcome {
+                Outcome::Fitted {
+                    degree: Some(degree),
+                    ..
+                }
+                | Outcome::Skipped {
+                    degree: Some(degree),
+                    ..
+                } => println!("row {row}: degree {degree}"),
+                Outcome::Fitted { degree: None, .. } => println!("row {row}: all zero"),
+                Outcome::Skipped { degree: None, .. } => println!("row {row}: no polynomial fit"),
+            }
+        }
+        match outcome {
+            Outcome::Fitted {
+                next, previous, at, ..
+            } => {
+                self.next += next;
+                self.previous += previous;
+                if let Some(at) = at {
+                    self.at += at;
+                }
+            }
+            Outcome::Skipped {
+                reason, residuals, ..
+            } => {
+                eprintln!("row {row}: skipped, {reason}");
+                if let Some((degree, residuals)) = residuals {
+                    let residuals = residuals
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                    eprintln!(
+                        "row {row}: residuals against degree {degree}: {}",
+                        residuals.join(" ")
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn read_rows(mut handle: impl FnMut(usize, &str) -> Result<()>) -> Result<()> {
+    let mut reader = BufReader::new(File::open("./day9/input.txt").context("error opening input")?);
+    let mut line = String::new();
+    let mut row = 0;
+    loop {
+        line.clear();
+        if reader
+            .read_line(&mut line)
+            .context("error reading from file")?
+            == 0
+        {
+            return Ok(());
+        }
+        row += 1;
+        if !line.trim().is_empty() {
+            handle(row, line.as_str())?;
+        }
+    }
+}
+
+fn process_serial(options: Options) -> Result<Totals> {
+    let mut worker = Worker::default();
+    let mut totals = Totals::default();
+    read_rows(|row, line| {
+        let outcome = worker
+            .process(line, options)
+            .with_context(|| format!("row {row}"))?;
+        totals.record(row, outcome, options);
+        Ok(())
+    })?;
+    Ok(totals)
+}
+
+type Batch = (usize, Vec<(usize, String)>);
+
+type Processed = Result<(usize, Vec<(usize, Outcome)>)>;
+
+fn run_worker(
+    batches: Arc<Mutex<Receiver<Batch>>>,
+    outcomes: SyncSender<Processed>,
+    options: Options,
+) {
+    let mut worker = Worker::default();
+    loop {
+        let (index, lines) = match batches.lock() {
+            Ok(receiver) => match receiver.recv() {
+                Ok(batch) => batch,
+                Err(_) => return,
+            },
+            Err(_) => return,
+        };
+        let processed = lines
+            .into_iter()
+            .map(|(row, line)| {
+                worker
+                    .process(line.as_str(), options)
+                    .with_context(|| format!("row {row}"))
+                    .map(|outcome| (row, outcome))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(|outcomes| (index, outcomes));
+        if outcomes.send(processed).is_err() {
+            return;
+        }
+    }
+}
+
+fn process_parallel(options: Options, threads: usize) -> Result<Totals> {
+    let (batch_tx, batch_rx) = sync_channel::<Batch>(threads * 2);
+    let (outcome_tx, outcome_rx) = sync_channel(threads * 2);
+    let batch_rx = Arc::new(Mutex::new(batch_rx));
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let batch_rx = batch_rx.clone();
+            let outcome_tx = outcome_tx.clone();
+            scope.spawn(move || run_worker(batch_rx, outcome_tx, options));
+        }
+        drop(batch_rx);
+
+        let (permit_tx, permit_rx) = sync_channel(threads * 2);
+        for _ in 0..threads * 2 {
+            permit_tx.send(()).context("error issuing batch permits")?;
+        }
+        let reader_errors = outcome_tx;
+        scope.spawn(move || {
+            let mut index = 0;
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            let mut send = |batch: Vec<(usize, String)>| {
+                permit_rx.recv().context("results stopped")?;
+                index += 1;
+                batch_tx.send((index - 1, batch)).context("workers stopped")
+            };
+            let result = read_rows(|row, line| {
+                batch.push((row, line.to_owned()));
+                if batch.len() == BATCH_SIZE {
+                    send(std::mem::replace(
+                        &mut batch,
+                        Vec::with_capacity(BATCH_SIZE),
+                    ))?;
+                }
+                Ok(())
+            })
+            .and_then(|()| send(batch));
+            if let Err(err) = result {
+                let _ = reader_errors.send(Err(err));
+            }
+        });
+
+        let mut totals = Totals::default();
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for processed in outcome_rx {
+            let (index, outcomes) = processed?;
+            pending.insert(index, outcomes);
+            while let Some(outcomes) = pending.remove(&next) {
+                for (row, outcome) in outcomes {
+                    totals.record(row, outcome, options);
+                }
+                let _ = permit_tx.send(());
+                next += 1;
+            }
+        }
+        Ok(totals)
+    })
+}
+
+fn main() -> Result<()> {
+    let arg_value = |name: &str| std::env::args().skip_while(|arg| arg != name).nth(1);
+    let options = Options {
+        max_degree: arg_value("--max-degree")
+            .map(|value| value.parse().context("error parsing --max-degree"))
+            .transpose()?,
+        at: arg_value("--at")
+            .map(|value| value.parse().context("error parsing --at"))
+            .transpose()?,
+        show_degrees: std::env::args().any(|arg| arg == "--degrees"),
+        show_residuals: std::env::args().any(|arg| arg == "--residuals"),
+    };
+    let threads = arg_value("--threads")
+        .map(|value| value.parse::<usize>().context("error parsing --threads"))
+        .transpose()?
+        .unwrap_or(1);
+
+    let totals = if threads > 1 {
+        process_parallel(options, threads)?
+    } else {
+        process_serial(options)?
+    };
+    println!("part1: {}", totals.next);
+    println!("part2: {}", totals.previous);
+    if let Some(index) = options.at {
+        println!("at {index}: {}", totals.at);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Polynomial;
+    use num_bigint::BigInt;
+
+    fn cubic(x: i64) -> i64 {
+        2 * x * x * x - 3 * x * x + x - 7
     }
 
-    let mut res = 0;
-    for line in stack.iter().rev() {
-        res = op(get(line)?, res);
+    #[test]
+    fn extrapolates_cubic_at_any_offset() {
+        let mut diffs = (0..8).map(|x| BigInt::from(cubic(x))).collect::<Vec<_>>();
+        let fit = Polynomial::fit_in_place(diffs.as_mut_slice());
+        assert!(fit.check(None).is_ok());
+        assert_eq!(fit.degree(), Some(3));
+        for index in [-5, -1, 0, 3, 10, 1000] {
+            assert_eq!(fit.at(index), BigInt::from(cubic(index)), "index {index}");
+        }
+        assert_eq!(fit.ahead(3), BigInt::from(cubic(10)));
+        assert_eq!(fit.behind(5), BigInt::from(cubic(-5)));
     }
-    Ok(res)
 }